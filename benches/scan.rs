@@ -0,0 +1,59 @@
+//! Times a scan of a generated folder tree on one thread and on every
+//! available core. Run with `cargo bench --bench scan`.
+
+use du_gui_rs::scanner::{self, ScanOptions};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const FANOUT: usize = 8;
+const DEPTH: usize = 4;
+const FILES_PER_FOLDER: usize = 5;
+const RUNS: u32 = 5;
+
+fn build_tree(dir: &Path, depth: usize) {
+    for f in 0..FILES_PER_FOLDER {
+        fs::write(dir.join(format!("file{}", f)), vec![0u8; 512 * (f + 1)]).unwrap();
+    }
+    if depth == 0 {
+        return;
+    }
+    for d in 0..FANOUT {
+        let child = dir.join(format!("dir{}", d));
+        fs::create_dir(&child).unwrap();
+        build_tree(&child, depth - 1);
+    }
+}
+
+fn time_scan(root: &Path, threads: usize) -> (Duration, usize) {
+    let options = ScanOptions {
+        threads,
+        ..ScanOptions::default()
+    };
+    // Warm the directory cache so the first run isn't the odd one out.
+    let folders = scanner::start(root, options.clone()).wait().entries.len();
+    let started = Instant::now();
+    for _ in 0..RUNS {
+        scanner::start(root, options.clone()).wait();
+    }
+    (started.elapsed() / RUNS, folders)
+}
+
+fn main() {
+    let dir = tempfile::tempdir().unwrap();
+    build_tree(dir.path(), DEPTH);
+
+    let cores = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let (single, folders) = time_scan(dir.path(), 1);
+    let (parallel, _) = time_scan(dir.path(), cores);
+
+    println!("{} folders, mean of {} runs", folders, RUNS);
+    println!("1 thread:   {:?}", single);
+    println!("{} threads: {:?}", cores, parallel);
+    println!(
+        "speedup:    {:.2}x",
+        single.as_secs_f64() / parallel.as_secs_f64()
+    );
+}
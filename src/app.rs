@@ -1,22 +1,39 @@
 use crate::styles;
+use crate::sunburst::Sunburst;
+use crate::treemap::Treemap;
 use arboard::Clipboard;
 use chrono::{DateTime, Local};
+use du_gui_rs::cleanup::{self, Action};
+use du_gui_rs::diff::{self, Change, DiffSort, FolderDiff};
+use du_gui_rs::export::{self, CsvDelimiter, CsvOptions};
+use du_gui_rs::ncdu::{self, NcduDump};
+use du_gui_rs::plan::{Plan, PlannedFolder};
+use du_gui_rs::scanner::{
+    self, ExclusionRules, FileEntry, HardLinkMode, ScanEvent, ScanOptions, SizeMode,
+};
+use du_gui_rs::snapshot::{self, Snapshot};
+use du_gui_rs::tree::ScanTree;
+use du_gui_rs::{aggregate_by_name, format_size, AggregatedEntry};
 use futures::channel::mpsc;
 use iced::alignment::Vertical;
 use iced::futures;
+use iced::futures::future::{self, Either};
 use iced::futures::{SinkExt, StreamExt};
 use iced::stream;
-use iced::widget::{button, checkbox, column, container, row, scrollable, stack, text, text_input};
+use iced::widget::{
+    button, canvas, checkbox, column, container, pick_list, progress_bar, row, scrollable, stack,
+    text, text_input, Space,
+};
 use iced::Subscription;
 use iced::{Alignment, Element, Length, Renderer, Task, Theme};
 use iced_aw::ContextMenu;
 use iced_table::table;
 use rfd::AsyncFileDialog;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use csv::Writer;
-
 #[derive(Debug, Clone)]
 pub enum Message {
     ShowAbout,
@@ -25,42 +42,127 @@ pub enum Message {
     CurrentUser,
     AllUsers,
     Scanned(FileEntry),
+    SkippedMount(String),
     Done,
     Stop,
     SyncHeader(scrollable::AbsoluteOffset),
     OpenFolder(String),
     OpenUrl(String),
     CopyPath(String),
+    ConfirmTrash(String),
+    CancelTrash,
+    MoveToTrash,
+    Trashed(String, Result<(), String>),
+    ConfirmDelete(String),
+    SetDeleteConfirmation(String),
+    CancelDelete,
+    DeletePermanently,
+    Deleted(String, Result<(), String>),
+    OpenDeletionLog,
+    SetInBasket(String, bool),
+    ClearBasket,
+    ConfirmBatch(Action),
+    PickMoveDestination,
+    PickArchive,
+    CancelBatch,
+    RunBatch,
+    BatchDone(Action, Vec<(PlannedFolder, Result<(), String>)>),
+    SavePlan,
+    PlanSaved(Result<Option<PathBuf>, String>),
+    OpenPlan,
+    PlanSelected(Option<PathBuf>),
+    PlanLoaded(PathBuf, Result<Plan, String>),
+    CancelPlan,
+    RunPlan,
     GoToSettings,
     SetEntriesVisible(String),
     SetShowLastAccessed(bool),
     SetShowHiddenFiles(bool),
+    SetScanThreads(String),
+    SetHardLinkMode(HardLinkMode),
+    SetOneFileSystem(bool),
+    SetNewExclusion(String),
+    AddExclusion,
+    RemoveExclusion(usize),
+    SetShowExcluded(bool),
+    SetNestedJson(bool),
+    SubmitSetting,
+    ResetSettings,
+    SetSizeMode(SizeMode),
+    SortBy(FileColumnKind),
+    ShowTree,
+    ToggleNode(PathBuf),
+    ShowTreemap,
+    TreemapDrill(PathBuf),
+    TreemapUp,
+    ShowSunburst,
+    SunburstZoom(PathBuf),
     OpenFolderDialog,
     FolderSelected(Option<PathBuf>),
     ExportCsv,
-    CsvExported,
+    SetCsvOptions(CsvOptions),
+    SaveCsv,
+    CloseCsvDialog,
+    CsvExported(Result<(), ExportFailure>),
+    ExportJson,
+    JsonExported(Result<(), ExportFailure>),
+    ExportNcdu,
+    NcduExported(Result<(), ExportFailure>),
+    ExportHtml,
+    HtmlExported(Result<(), ExportFailure>),
+    RetryExport,
+    DismissExportError,
+    SaveSnapshot,
+    SnapshotSaved(Result<Option<PathBuf>, ExportFailure>),
+    OpenSnapshotDialog,
+    SnapshotSelected(Option<PathBuf>),
+    SnapshotLoaded(PathBuf, Result<Snapshot, String>),
+    CompareWithSnapshot,
+    BaselineSelected(Option<PathBuf>),
+    BaselineLoaded(PathBuf, Result<Snapshot, String>),
+    SetDiffSort(DiffSort),
+    ImportNcdu,
+    NcduSelected(Option<PathBuf>),
+    NcduImported(PathBuf, Result<NcduDump, String>),
     ShowWaitDialog,
     CloseWaitDialog,
     ShowNewScreen,
+    StartScan(PathBuf, ScanOptions),
+    CloseRequested,
 }
 
-#[derive(Clone, Debug)]
-pub struct FileEntry {
-    pub file: String,
-    pub size: u64,
-    pub accessed: Option<DateTime<Local>>,
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ncdu,
+    Html,
+    Snapshot,
 }
 
-struct AggregatedEntry {
-    pub name: String,
-    pub total_size: u64,
-    pub count: usize,
+/// An export that could not be written, kept so it can be retried to the
+/// same file.
+#[derive(Debug, Clone)]
+pub struct ExportFailure {
+    format: ExportFormat,
+    path: PathBuf,
+    error: String,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppSettings {
     entries_visible: usize,
     show_last_accessed: bool,
     show_hidden_files: bool,
+    scan_threads: usize,
+    hard_links: HardLinkMode,
+    one_file_system: bool,
+    exclusions: Vec<String>,
+    show_excluded: bool,
+    size_mode: SizeMode,
+    nested_json: bool,
+    csv_options: CsvOptions,
 }
 
 impl Default for AppSettings {
@@ -69,6 +171,107 @@ impl Default for AppSettings {
             entries_visible: 20,
             show_last_accessed: true,
             show_hidden_files: false,
+            scan_threads: ScanOptions::default().threads,
+            hard_links: HardLinkMode::default(),
+            one_file_system: false,
+            exclusions: Vec::new(),
+            show_excluded: false,
+            size_mode: SizeMode::default(),
+            nested_json: false,
+            csv_options: CsvOptions::default(),
+        }
+    }
+}
+
+/// Bump when a setting changes meaning, and convert older files in
+/// `AppSettings::load`.
+const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct SettingsFile {
+    version: u32,
+    #[serde(flatten)]
+    settings: AppSettings,
+}
+
+impl AppSettings {
+    fn file() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("FindBigFolders").join("settings.json"))
+    }
+
+    /// Reads the saved settings, falling back to the defaults for anything
+    /// missing or unusable.
+    pub fn load() -> Self {
+        Self::file()
+            .and_then(|file| std::fs::read_to_string(file).ok())
+            .map(|json| Self::from_json(&json))
+            .unwrap_or_default()
+    }
+
+    /// Keeps every saved setting that can be read, so one bad value only
+    /// resets that setting. A corrupt file, or one written by a newer version
+    /// whose settings may mean something else, gives the defaults.
+    fn from_json(json: &str) -> Self {
+        let Ok(serde_json::Value::Object(saved)) = serde_json::from_str(json) else {
+            return Self::default();
+        };
+        let readable = saved
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .is_some_and(|version| version <= u64::from(SETTINGS_VERSION));
+        if !readable {
+            return Self::default();
+        }
+        let Ok(serde_json::Value::Object(mut settings)) = serde_json::to_value(Self::default())
+        else {
+            return Self::default();
+        };
+        for (key, value) in saved {
+            if !settings.contains_key(&key) {
+                continue;
+            }
+            let previous = settings.insert(key.clone(), value);
+            if serde_json::from_value::<Self>(settings.clone().into()).is_err() {
+                if let Some(previous) = previous {
+                    settings.insert(key, previous);
+                }
+            }
+        }
+        serde_json::from_value::<Self>(settings.into())
+            .unwrap_or_default()
+            .validated()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(file) = Self::file() else {
+            return Ok(());
+        };
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(&SettingsFile {
+            version: SETTINGS_VERSION,
+            settings: self.clone(),
+        })?;
+        std::fs::write(file, json)
+    }
+
+    fn validated(mut self) -> Self {
+        if self.scan_threads == 0 {
+            self.scan_threads = Self::default().scan_threads;
+        }
+        self.exclusions
+            .retain(|pattern| ExclusionRules::new(std::slice::from_ref(pattern)).is_ok());
+        self
+    }
+
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            threads: self.scan_threads,
+            hard_links: self.hard_links,
+            one_file_system: self.one_file_system,
+            exclusions: ExclusionRules::new(&self.exclusions).unwrap_or_default(),
+            show_excluded: self.show_excluded,
         }
     }
 }
@@ -76,9 +279,12 @@ impl Default for AppSettings {
 pub struct AppState {
     mode: Mode,
     entries: Vec<FileEntry>,
+    excluded_count: usize,
     aggregated_entries: Vec<AggregatedEntry>,
     sort_cutoff: usize,
     scanning: bool,
+    // A trash, delete or batch cleanup is running in the background.
+    applying: bool,
     search_tx: Option<mpsc::Sender<Message>>,
     stop_tx: Option<mpsc::Sender<Message>>,
     columns: Vec<FileColumn>,
@@ -86,8 +292,34 @@ pub struct AppState {
     header: scrollable::Id,
     body: scrollable::Id,
     settings: AppSettings,
+    settings_changed: bool,
     status: String,
     show_wait_dialog: bool,
+    show_csv_dialog: bool,
+    pending_trash: Option<String>,
+    pending_delete: Option<String>,
+    delete_confirmation: String,
+    // Basket folders and their size on disk when they were added.
+    basket: HashMap<String, u64>,
+    pending_batch: Option<Action>,
+    pending_plan: Option<Plan>,
+    sort_by: FileColumnKind,
+    scan_options: Option<ScanOptions>,
+    skipped_mounts: Vec<String>,
+    scan_root: Option<PathBuf>,
+    scanned_at: Option<DateTime<Local>>,
+    tree: ScanTree,
+    expanded: HashSet<PathBuf>,
+    treemap_root: Option<PathBuf>,
+    sunburst_root: Option<PathBuf>,
+    new_exclusion: String,
+    exclusion_error: Option<String>,
+    export_failure: Option<ExportFailure>,
+    loaded_from: Option<PathBuf>,
+    recent_snapshots: Vec<PathBuf>,
+    baseline: Option<Snapshot>,
+    diff: Vec<FolderDiff>,
+    diff_sort: DiffSort,
 }
 
 impl Default for AppState {
@@ -95,14 +327,19 @@ impl Default for AppState {
         Self {
             mode: Mode::default(),
             entries: Vec::new(),
+            excluded_count: 0,
             aggregated_entries: Vec::new(),
             sort_cutoff: 1000,
             scanning: false,
+            applying: false,
             search_tx: None,
             stop_tx: None,
             columns: vec![
+                FileColumn::new(FileColumnKind::Select),
                 FileColumn::new(FileColumnKind::File),
                 FileColumn::new(FileColumnKind::Size),
+                FileColumn::new(FileColumnKind::Files),
+                FileColumn::new(FileColumnKind::Folders),
                 FileColumn::new(FileColumnKind::AccessTime),
             ],
             aggregated_columns: vec![
@@ -113,8 +350,33 @@ impl Default for AppState {
             header: scrollable::Id::unique(),
             body: scrollable::Id::unique(),
             settings: AppSettings::default(),
+            settings_changed: false,
             status: String::new(),
             show_wait_dialog: false,
+            show_csv_dialog: false,
+            pending_trash: None,
+            pending_delete: None,
+            delete_confirmation: String::new(),
+            basket: HashMap::new(),
+            pending_batch: None,
+            pending_plan: None,
+            sort_by: FileColumnKind::Size,
+            scan_options: None,
+            skipped_mounts: Vec::new(),
+            scan_root: None,
+            scanned_at: None,
+            tree: ScanTree::default(),
+            expanded: HashSet::new(),
+            treemap_root: None,
+            sunburst_root: None,
+            new_exclusion: String::new(),
+            exclusion_error: None,
+            export_failure: None,
+            loaded_from: None,
+            recent_snapshots: Vec::new(),
+            baseline: None,
+            diff: Vec::new(),
+            diff_sort: DiffSort::default(),
         }
     }
 }
@@ -126,6 +388,10 @@ pub enum Mode {
     About,
     Settings,
     NewScreen,
+    Tree,
+    Treemap,
+    Sunburst,
+    Diff,
 }
 
 struct AggregatedColumn {
@@ -147,23 +413,39 @@ enum AggregatedColumnKind {
 struct FileColumn {
     kind: FileColumnKind,
     width: f32,
+    size_mode: SizeMode,
+    sorted: bool,
+    basket: HashSet<String>,
 }
 
 impl FileColumn {
     fn new(kind: FileColumnKind) -> Self {
         let width = match kind {
+            FileColumnKind::Select => 30.0,
             FileColumnKind::File => 500.0,
             FileColumnKind::Size => 100.0,
+            FileColumnKind::Files => 100.0,
+            FileColumnKind::Folders => 100.0,
             FileColumnKind::AccessTime => 150.0,
         };
 
-        Self { kind, width }
+        Self {
+            kind,
+            width,
+            size_mode: SizeMode::default(),
+            sorted: kind == FileColumnKind::Size,
+            basket: HashSet::new(),
+        }
     }
 }
 
-enum FileColumnKind {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileColumnKind {
+    Select,
     File,
     Size,
+    Files,
+    Folders,
     AccessTime,
 }
 
@@ -172,12 +454,32 @@ impl<'a> table::Column<'a, Message, Theme, Renderer> for FileColumn {
 
     fn header(&'a self, _col_index: usize) -> Element<'a, Message> {
         let content = match self.kind {
+            FileColumnKind::Select => "",
             FileColumnKind::File => "Folder",
-            FileColumnKind::Size => "Size",
+            FileColumnKind::Size => match self.size_mode {
+                SizeMode::OnDisk => "Size",
+                SizeMode::Apparent => "Apparent Size",
+            },
+            FileColumnKind::Files => "Files",
+            FileColumnKind::Folders => "Subfolders",
             FileColumnKind::AccessTime => "Last Accessed",
         };
 
-        container(text(content)).align_y(Vertical::Center).into()
+        match self.kind {
+            FileColumnKind::Size | FileColumnKind::Files | FileColumnKind::Folders => {
+                let label = if self.sorted {
+                    format!("{} ▼", content)
+                } else {
+                    content.to_string()
+                };
+                button(text(label))
+                    .style(button::text)
+                    .padding(0)
+                    .on_press(Message::SortBy(self.kind))
+                    .into()
+            }
+            _ => container(text(content)).align_y(Vertical::Center).into(),
+        }
     }
 
     fn cell(
@@ -187,6 +489,16 @@ impl<'a> table::Column<'a, Message, Theme, Renderer> for FileColumn {
         row: &'a FileEntry,
     ) -> Element<'a, Message> {
         let content: Element<_> = match self.kind {
+            FileColumnKind::Select => {
+                let path = row.file.clone();
+                checkbox("", self.basket.contains(&row.file))
+                    .on_toggle_maybe(if row.excluded {
+                        None
+                    } else {
+                        Some(move |selected| Message::SetInBasket(path.clone(), selected))
+                    })
+                    .into()
+            }
             FileColumnKind::File => {
                 let btn = text(&row.file);
                 let path = row.file.clone();
@@ -201,12 +513,25 @@ impl<'a> table::Column<'a, Message, Theme, Renderer> for FileColumn {
                         button("Search inside folder")
                             .on_press(Message::FolderSelected(Some(path.clone().into())))
                             .into(),
+                        button("Move to Trash")
+                            .on_press(Message::ConfirmTrash(path.clone()))
+                            .into(),
+                        button("Delete Permanently")
+                            .on_press(Message::ConfirmDelete(path.clone()))
+                            .into(),
                     ])
                     .into()
                 })
                 .into()
             }
-            FileColumnKind::Size => text(format_size(row.size)).into(),
+            FileColumnKind::Size => text(if row.excluded {
+                "excluded".to_string()
+            } else {
+                format_size(row.size_for(self.size_mode))
+            })
+            .into(),
+            FileColumnKind::Files => text(row.files.to_string()).into(),
+            FileColumnKind::Folders => text(row.dirs.to_string()).into(),
             FileColumnKind::AccessTime => text(if let Some(accessed_dt) = row.accessed {
                 accessed_dt.format("%Y-%m-%d %H:%M").to_string()
             } else {
@@ -278,10 +603,35 @@ const ABOUT_TEXT: &str = "Quickly find out which folders are taking space on you
 
 impl AppState {
     pub fn new() -> (Self, Task<Message>) {
-        (Self::default(), Task::none())
+        let mut state = Self {
+            settings: AppSettings::load(),
+            recent_snapshots: load_recent_snapshots(),
+            ..Self::default()
+        };
+        for column in &mut state.columns {
+            column.size_mode = state.settings.size_mode;
+        }
+        (state, Task::none())
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
+        // Wait for the next message after typing, such as pressing Enter or
+        // closing the window, rather than writing the file on every keystroke.
+        let typing = matches!(
+            message,
+            Message::SetEntriesVisible(_) | Message::SetScanThreads(_)
+        );
+        let task = self.handle(message);
+        if self.settings_changed && !typing {
+            self.settings_changed = false;
+            if let Err(e) = self.settings.save() {
+                self.status = format!("Could not save settings: {}", e);
+            }
+        }
+        task
+    }
+
+    fn handle(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::ShowAbout => {
                 self.mode = Mode::About;
@@ -294,17 +644,15 @@ impl AppState {
                 self.stop_tx = Some(tx2);
             }
             Message::CurrentUser => {
-                self.entries.clear();
-                if let Some(tx) = &mut self.search_tx {
-                    self.scanning = true;
-                    let _ = tx.try_send(Message::CurrentUser);
+                if let Some(dir) = dirs::home_dir() {
+                    self.start_scan(dir);
                 }
             }
             Message::AllUsers => {
-                self.entries.clear();
-                if let Some(tx) = &mut self.search_tx {
-                    self.scanning = true;
-                    let _ = tx.try_send(Message::AllUsers);
+                if let Some(dir) = dirs::home_dir() {
+                    if let Some(parent_dir) = dir.parent() {
+                        self.start_scan(parent_dir.to_path_buf());
+                    }
                 }
             }
             Message::Scanned(entry) => {
@@ -313,6 +661,9 @@ impl AppState {
                     self.bake_entries();
                 }
             }
+            Message::SkippedMount(path) => {
+                self.skipped_mounts.push(path);
+            }
             Message::Stop => {
                 if let Some(tx) = &mut self.stop_tx {
                     let _ = tx.try_send(Message::Stop);
@@ -327,6 +678,387 @@ impl AppState {
             Message::OpenUrl(url) => {
                 let _ = webbrowser::open(&url);
             }
+            Message::ConfirmTrash(path) => {
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                } else if self.is_protected(&path) {
+                    self.status = format!("{} is protected and can't be removed", path);
+                } else {
+                    self.pending_trash = Some(path);
+                }
+            }
+            Message::CancelTrash => {
+                self.pending_trash = None;
+            }
+            Message::MoveToTrash => {
+                if let Some(path) = self.pending_trash.take() {
+                    self.applying = true;
+                    return Task::perform(
+                        async move {
+                            let target = path.clone();
+                            let result =
+                                blocking(move || trash::delete(&target).map_err(|e| e.to_string()))
+                                    .await;
+                            (path, result)
+                        },
+                        |(path, result)| Message::Trashed(path, result),
+                    );
+                }
+            }
+            Message::Trashed(path, Ok(())) => {
+                self.applying = false;
+                self.remove_folder(Path::new(&path));
+            }
+            Message::Trashed(path, Err(e)) => {
+                self.applying = false;
+                self.status = format!("Could not move {} to the trash: {}", path, e);
+            }
+            Message::ConfirmDelete(path) => {
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                } else if self.is_protected(&path) {
+                    self.status = format!("{} is protected and can't be removed", path);
+                } else {
+                    self.delete_confirmation.clear();
+                    self.pending_delete = Some(path);
+                }
+            }
+            Message::SetDeleteConfirmation(value) => {
+                self.delete_confirmation = value;
+            }
+            Message::CancelDelete => {
+                self.pending_delete = None;
+            }
+            Message::DeletePermanently => {
+                let Some(path) = self.pending_delete.take() else {
+                    return Task::none();
+                };
+                let name = folder_name(&path);
+                if name.is_empty() || self.delete_confirmation != name || self.is_protected(&path) {
+                    return Task::none();
+                }
+                self.applying = true;
+                return Task::perform(
+                    async move {
+                        let target = path.clone();
+                        let result = blocking(move || {
+                            std::fs::remove_dir_all(&target).map_err(|e| e.to_string())
+                        })
+                        .await;
+                        (path, result)
+                    },
+                    |(path, result)| Message::Deleted(path, result),
+                );
+            }
+            Message::Deleted(path, Ok(())) => {
+                self.applying = false;
+                let reclaimed = self
+                    .remove_folder(Path::new(&path))
+                    .map(|entry| entry.size)
+                    .unwrap_or(0);
+                self.status = format!("Deleted {}, reclaiming {}", path, format_size(reclaimed));
+                if let Err(e) = cleanup::log_deletion(Path::new(&path), reclaimed) {
+                    self.status += &format!(". Could not write to the deletion log: {}", e);
+                }
+            }
+            Message::Deleted(path, Err(e)) => {
+                self.applying = false;
+                // Part of the folder may be gone, only a rescan can tell.
+                self.status = format!(
+                    "Could not delete all of {}: {}. Rescan to see what is left.",
+                    path, e
+                );
+            }
+            Message::OpenDeletionLog => {
+                if let Some(log) = cleanup::deletion_log() {
+                    let _ = opener::open(log);
+                }
+            }
+            Message::SetInBasket(path, selected) => {
+                if selected {
+                    let size = self
+                        .entries
+                        .iter()
+                        .find(|entry| entry.file == path)
+                        .map(|entry| entry.size)
+                        .unwrap_or(0);
+                    self.basket.insert(path, size);
+                } else {
+                    self.basket.remove(&path);
+                }
+                self.sync_basket();
+            }
+            Message::ClearBasket => {
+                self.basket.clear();
+                self.sync_basket();
+            }
+            Message::ConfirmBatch(action) => {
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                } else if let Some(folder) =
+                    cleanup::conflicting_folder(&action, &self.basket_paths())
+                {
+                    self.status = format!(
+                        "The destination is inside {}, which is in the basket",
+                        folder.display()
+                    );
+                } else if let Some(path) = self
+                    .basket_paths()
+                    .iter()
+                    .find(|path| self.is_protected(&path.to_string_lossy()))
+                {
+                    self.status = format!("{} is protected and can't be removed", path.display());
+                } else {
+                    self.delete_confirmation.clear();
+                    self.pending_batch = Some(action);
+                }
+            }
+            Message::PickMoveDestination => {
+                return Task::perform(
+                    AsyncFileDialog::new()
+                        .set_show_hidden_files(true)
+                        .set_title("Move the folders into")
+                        .pick_folder(),
+                    |handle| match handle {
+                        Some(handle) => {
+                            Message::ConfirmBatch(Action::Move(handle.path().to_path_buf()))
+                        }
+                        None => Message::CancelBatch,
+                    },
+                );
+            }
+            Message::PickArchive => {
+                let filename = format!(
+                    "findbigfolders_{}.tar.gz",
+                    Local::now().format("%Y-%m-%d_%H-%M-%S")
+                );
+                return Task::perform(
+                    AsyncFileDialog::new()
+                        .add_filter("Archive", &["gz"])
+                        .set_show_hidden_files(true)
+                        .set_file_name(&filename)
+                        .save_file(),
+                    |handle| match handle {
+                        Some(handle) => {
+                            Message::ConfirmBatch(Action::Compress(handle.path().to_path_buf()))
+                        }
+                        None => Message::CancelBatch,
+                    },
+                );
+            }
+            Message::CancelBatch => {
+                self.pending_batch = None;
+            }
+            Message::RunBatch => {
+                if !self
+                    .pending_batch
+                    .as_ref()
+                    .is_some_and(|action| self.delete_confirmed(action))
+                {
+                    return Task::none();
+                }
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                    return Task::none();
+                }
+                let Some(action) = self.pending_batch.take() else {
+                    return Task::none();
+                };
+                let folders = self.basket_folders();
+                if let Some(folder) = folders
+                    .iter()
+                    .find(|folder| self.is_protected(&folder.path.to_string_lossy()))
+                {
+                    self.status = format!(
+                        "{} is protected and can't be removed",
+                        folder.path.display()
+                    );
+                    return Task::none();
+                }
+                self.applying = true;
+                return run_batch(action, folders);
+            }
+            Message::BatchDone(action, results) => {
+                self.applying = false;
+                let mut done = 0;
+                let mut reclaimed = 0;
+                let mut failures = Vec::new();
+                let mut log_error = None;
+                for (folder, result) in results {
+                    match result {
+                        Ok(()) => {
+                            // Plans can be run against folders that aren't in
+                            // the results, so go by the size they were planned with.
+                            let size = self
+                                .remove_folder(&folder.path)
+                                .map(|entry| entry.size)
+                                .unwrap_or(folder.size);
+                            if action == Action::Delete {
+                                if let Err(e) = cleanup::log_deletion(&folder.path, size) {
+                                    log_error = Some(e);
+                                }
+                            }
+                            done += 1;
+                            reclaimed += size;
+                        }
+                        Err(e) => failures.push(format!("{}: {}", folder.path.display(), e)),
+                    }
+                }
+                self.status = match &action {
+                    Action::Trash => format!(
+                        "Moved {} folders to the trash, reclaiming {} once it is emptied",
+                        done,
+                        format_size(reclaimed)
+                    ),
+                    Action::Delete => format!(
+                        "Deleted {} folders, reclaiming {}",
+                        done,
+                        format_size(reclaimed)
+                    ),
+                    Action::Move(destination) => format!(
+                        "Moved {} folders ({}) to {}",
+                        done,
+                        format_size(reclaimed),
+                        destination.display()
+                    ),
+                    Action::Compress(archive) => {
+                        let archive_size = std::fs::metadata(archive).map(|m| m.len()).unwrap_or(0);
+                        format!(
+                            "Compressed {} folders into {}, reclaiming {}",
+                            done,
+                            archive.display(),
+                            format_size(reclaimed.saturating_sub(archive_size))
+                        )
+                    }
+                };
+                if let Some(first) = failures.first() {
+                    self.status += &format!(". {} failed, first {}", failures.len(), first);
+                    if action == Action::Delete {
+                        // Part of a folder may be gone, only a rescan can tell.
+                        self.status += ". Rescan to see what is left.";
+                    }
+                }
+                if let Some(e) = log_error {
+                    self.status += &format!(". Could not write to the deletion log: {}", e);
+                }
+            }
+            Message::SavePlan => {
+                let (Some(action), Some(root)) =
+                    (self.pending_batch.take(), self.scan_root.clone())
+                else {
+                    return Task::none();
+                };
+                let plan = Plan::new(root, action, self.basket_folders());
+                let filename = format!(
+                    "findbigfolders_plan_{}.json",
+                    plan.created_at.format("%Y-%m-%d_%H-%M-%S")
+                );
+                return Task::perform(
+                    async move {
+                        let Some(path) = AsyncFileDialog::new()
+                            .add_filter("Cleanup plan", &["json"])
+                            .add_filter("Shell script", &["sh"])
+                            .set_show_hidden_files(true)
+                            .set_file_name(&filename)
+                            .save_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                        else {
+                            return Ok(None);
+                        };
+                        plan.save(&path)
+                            .map(|()| Some(path))
+                            .map_err(|e| e.to_string())
+                    },
+                    Message::PlanSaved,
+                );
+            }
+            Message::PlanSaved(Ok(Some(path))) => {
+                self.status = format!(
+                    "Saved the cleanup plan to {}, nothing has been removed",
+                    path.display()
+                );
+            }
+            Message::PlanSaved(Ok(None)) => {}
+            Message::PlanSaved(Err(e)) => {
+                self.status = format!("Could not save the cleanup plan: {}", e);
+            }
+            Message::OpenPlan => {
+                return Task::perform(
+                    async {
+                        AsyncFileDialog::new()
+                            .add_filter("Cleanup plan", &["json"])
+                            .set_show_hidden_files(true)
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::PlanSelected,
+                );
+            }
+            Message::PlanSelected(Some(path)) => {
+                return Task::perform(
+                    async move {
+                        let plan = path.clone();
+                        let result =
+                            blocking(move || Plan::load(&plan).map_err(|e| e.to_string())).await;
+                        (path, result)
+                    },
+                    |(path, result)| Message::PlanLoaded(path, result),
+                );
+            }
+            Message::PlanSelected(None) => {}
+            Message::PlanLoaded(_, Ok(plan)) => {
+                self.delete_confirmation.clear();
+                self.pending_plan = Some(plan);
+            }
+            Message::PlanLoaded(path, Err(e)) => {
+                self.status = format!("Could not open {}: {}", path.display(), e);
+            }
+            Message::CancelPlan => {
+                self.pending_plan = None;
+            }
+            Message::RunPlan => {
+                if !self
+                    .pending_plan
+                    .as_ref()
+                    .is_some_and(|plan| self.delete_confirmed(&plan.action))
+                {
+                    return Task::none();
+                }
+                // Keep the plan open to run once the scan or cleanup is done.
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                    return Task::none();
+                }
+                let Some(plan) = self.pending_plan.take() else {
+                    return Task::none();
+                };
+                if let Some(folder) = plan
+                    .folders
+                    .iter()
+                    .find(|folder| cleanup::is_protected(&folder.path, &plan.root))
+                {
+                    self.status = format!(
+                        "{} is protected and can't be removed",
+                        folder.path.display()
+                    );
+                    return Task::none();
+                }
+                let paths: Vec<PathBuf> = plan
+                    .folders
+                    .iter()
+                    .map(|folder| folder.path.clone())
+                    .collect();
+                let outermost = cleanup::outermost(&paths);
+                let folders = plan
+                    .folders
+                    .into_iter()
+                    .filter(|folder| outermost.contains(&folder.path))
+                    .collect();
+                self.applying = true;
+                return run_batch(plan.action, folders);
+            }
             Message::CopyPath(path) => {
                 if let Ok(mut clipboard) = Clipboard::new() {
                     let _ = clipboard.set_text(&path);
@@ -338,17 +1070,97 @@ impl AppState {
             Message::SetEntriesVisible(value) => {
                 if let Ok(num) = value.parse::<usize>() {
                     self.settings.entries_visible = num;
+                    self.settings_changed = true;
                 }
             }
             Message::SetShowLastAccessed(value) => {
                 self.settings.show_last_accessed = value;
+                self.settings_changed = true;
             }
             Message::SetShowHiddenFiles(value) => {
                 self.settings.show_hidden_files = value;
+                self.settings_changed = true;
+            }
+            Message::SetScanThreads(value) => {
+                if let Ok(num) = value.parse::<usize>() {
+                    if num > 0 {
+                        self.settings.scan_threads = num;
+                        self.settings_changed = true;
+                    }
+                }
+            }
+            // Only ends the typing, `update` then saves what was typed.
+            Message::SubmitSetting => {}
+            Message::SetHardLinkMode(mode) => {
+                self.settings.hard_links = mode;
+                self.settings_changed = true;
+            }
+            Message::SetOneFileSystem(value) => {
+                self.settings.one_file_system = value;
+                self.settings_changed = true;
+            }
+            Message::SetNewExclusion(value) => {
+                self.new_exclusion = value;
+                self.exclusion_error = None;
+            }
+            Message::AddExclusion => {
+                let pattern = self.new_exclusion.trim().to_string();
+                if !pattern.is_empty() {
+                    match ExclusionRules::new(std::slice::from_ref(&pattern)) {
+                        Ok(_) => {
+                            self.settings.exclusions.push(pattern);
+                            self.settings_changed = true;
+                            self.new_exclusion.clear();
+                            self.exclusion_error = None;
+                        }
+                        Err(e) => {
+                            self.exclusion_error = Some(e);
+                        }
+                    }
+                }
+            }
+            Message::RemoveExclusion(index) => {
+                if index < self.settings.exclusions.len() {
+                    self.settings.exclusions.remove(index);
+                    self.settings_changed = true;
+                }
+            }
+            Message::SetShowExcluded(value) => {
+                self.settings.show_excluded = value;
+                self.settings_changed = true;
+            }
+            Message::SetNestedJson(value) => {
+                self.settings.nested_json = value;
+                self.settings_changed = true;
+            }
+            Message::ResetSettings => {
+                self.settings = AppSettings::default();
+                self.settings_changed = true;
+                self.new_exclusion.clear();
+                self.exclusion_error = None;
+                return self.handle(Message::SetSizeMode(self.settings.size_mode));
+            }
+            Message::SetSizeMode(mode) => {
+                self.settings.size_mode = mode;
+                self.settings_changed = true;
+                for column in &mut self.columns {
+                    column.size_mode = mode;
+                }
+                self.bake_entries();
+                self.build_tree();
+                self.build_diff();
+            }
+            Message::SortBy(kind) => {
+                self.sort_by = kind;
+                for column in &mut self.columns {
+                    column.sorted = column.kind == kind;
+                }
+                self.bake_entries();
             }
             Message::Done => {
                 self.scanning = false;
                 self.bake_entries();
+                self.build_tree();
             }
             Message::OpenFolderDialog => {
                 let show_hidden = self.settings.show_hidden_files;
@@ -367,28 +1179,217 @@ impl AppState {
                 );
             }
             Message::FolderSelected(path) => {
-                if self.scanning {
+                if self.busy() {
                     self.show_wait_dialog = true;
                     return Task::none();
                 }
                 if let Some(p) = path {
-                    self.entries.clear();
-                    if let Some(tx) = &mut self.search_tx {
-                        self.scanning = true;
-                        let _ = tx.try_send(Message::FolderSelected(Some(p)));
-                    }
+                    self.start_scan(p);
                 }
             }
             Message::ExportCsv => {
-                let entries = self.entries.clone();
+                self.show_csv_dialog = true;
+            }
+            Message::SetCsvOptions(options) => {
+                self.settings.csv_options = options;
+                self.settings_changed = true;
+            }
+            Message::SaveCsv => {
+                self.show_csv_dialog = false;
+                return self.export_csv(None);
+            }
+            Message::CloseCsvDialog => {
+                self.show_csv_dialog = false;
+            }
+            Message::ExportJson => {
+                return self.export_json(None);
+            }
+            Message::ExportNcdu => {
+                return self.export_ncdu(None);
+            }
+            Message::ExportHtml => {
+                return self.export_html(None);
+            }
+            Message::CsvExported(result)
+            | Message::JsonExported(result)
+            | Message::NcduExported(result)
+            | Message::HtmlExported(result) => {
+                self.export_failure = result.err();
+            }
+            Message::RetryExport => {
+                if let Some(failure) = self.export_failure.take() {
+                    return match failure.format {
+                        ExportFormat::Csv => self.export_csv(Some(failure.path)),
+                        ExportFormat::Json => self.export_json(Some(failure.path)),
+                        ExportFormat::Ncdu => self.export_ncdu(Some(failure.path)),
+                        ExportFormat::Html => self.export_html(Some(failure.path)),
+                        ExportFormat::Snapshot => self.save_snapshot(Some(failure.path)),
+                    };
+                }
+            }
+            Message::DismissExportError => {
+                self.export_failure = None;
+            }
+            Message::SaveSnapshot => {
+                return self.save_snapshot(None);
+            }
+            Message::SnapshotSaved(result) => match result {
+                Ok(Some(path)) => self.remember_snapshot(path),
+                Ok(None) => {}
+                Err(failure) => self.export_failure = Some(failure),
+            },
+            Message::OpenSnapshotDialog => {
+                return Task::perform(
+                    async {
+                        AsyncFileDialog::new()
+                            .add_filter("Snapshot", &[snapshot::EXTENSION])
+                            .set_show_hidden_files(true)
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::SnapshotSelected,
+                );
+            }
+            Message::SnapshotSelected(path) => {
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                    return Task::none();
+                }
+                if let Some(path) = path {
+                    self.status = format!("Opening {}", path.display());
+                    return Task::perform(
+                        async move {
+                            let snapshot = path.clone();
+                            let result = blocking(move || {
+                                Snapshot::load(&snapshot).map_err(|e| e.to_string())
+                            })
+                            .await;
+                            (path, result)
+                        },
+                        |(path, result)| Message::SnapshotLoaded(path, result),
+                    );
+                }
+            }
+            Message::SnapshotLoaded(path, Ok(snapshot)) => {
+                self.clear_results();
+                self.entries = snapshot.entries;
+                self.skipped_mounts = snapshot.skipped_mounts;
+                self.scan_root = Some(snapshot.root);
+                self.scanned_at = Some(snapshot.scanned_at);
+                self.scan_options = snapshot.options;
+                self.loaded_from = Some(path.clone());
+                self.remember_snapshot(path);
+                self.bake_entries();
+                self.build_tree();
+            }
+            Message::CompareWithSnapshot => {
+                return Task::perform(
+                    async {
+                        AsyncFileDialog::new()
+                            .add_filter("Snapshot", &[snapshot::EXTENSION])
+                            .set_show_hidden_files(true)
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
+                    },
+                    Message::BaselineSelected,
+                );
+            }
+            Message::BaselineSelected(Some(path)) => {
+                self.status = format!("Opening {}", path.display());
                 return Task::perform(
                     async move {
-                        export_csv(entries).await;
+                        let snapshot = path.clone();
+                        let result =
+                            blocking(move || Snapshot::load(&snapshot).map_err(|e| e.to_string()))
+                                .await;
+                        (path, result)
+                    },
+                    |(path, result)| Message::BaselineLoaded(path, result),
+                );
+            }
+            Message::BaselineSelected(None) => {}
+            Message::BaselineLoaded(path, Ok(baseline)) => {
+                if Some(&baseline.root) != self.scan_root.as_ref() {
+                    self.bake_entries();
+                    self.status = format!(
+                        "{} is a snapshot of {}, not of the scanned folder",
+                        path.display(),
+                        baseline.root.display()
+                    );
+                    return Task::none();
+                }
+                self.baseline = Some(baseline);
+                self.build_diff();
+                self.mode = Mode::Diff;
+            }
+            Message::BaselineLoaded(path, Err(e)) => {
+                self.bake_entries();
+                self.status = format!("Could not open snapshot {}: {}", path.display(), e);
+            }
+            Message::SetDiffSort(sort) => {
+                self.diff_sort = sort;
+                diff::sort(&mut self.diff, sort);
+            }
+            Message::ImportNcdu => {
+                return Task::perform(
+                    async {
+                        AsyncFileDialog::new()
+                            .add_filter("ncdu export", &["json"])
+                            .set_show_hidden_files(true)
+                            .pick_file()
+                            .await
+                            .map(|handle| handle.path().to_path_buf())
                     },
-                    |_| Message::CsvExported,
+                    Message::NcduSelected,
                 );
             }
-            Message::CsvExported => {}
+            Message::NcduSelected(path) => {
+                if self.busy() {
+                    self.show_wait_dialog = true;
+                    return Task::none();
+                }
+                if let Some(path) = path {
+                    self.status = format!("Opening {}", path.display());
+                    let hard_links = self.settings.hard_links;
+                    return Task::perform(
+                        async move {
+                            let dump = path.clone();
+                            let result = blocking(move || {
+                                ncdu::read(&dump, hard_links).map_err(|e| e.to_string())
+                            })
+                            .await;
+                            (path, result)
+                        },
+                        |(path, result)| Message::NcduImported(path, result),
+                    );
+                }
+            }
+            Message::NcduImported(path, Ok(dump)) => {
+                self.clear_results();
+                self.entries = dump.entries;
+                self.skipped_mounts = dump
+                    .skipped_mounts
+                    .iter()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .collect();
+                self.scan_root = Some(dump.root);
+                self.scanned_at = Some(dump.scanned_at.unwrap_or_else(Local::now));
+                // ncdu doesn't record how the scan was made.
+                self.scan_options = None;
+                self.loaded_from = Some(path);
+                self.bake_entries();
+                self.build_tree();
+            }
+            Message::NcduImported(path, Err(e)) => {
+                self.status = format!("Could not import {}: {}", path.display(), e);
+            }
+            Message::SnapshotLoaded(path, Err(e)) => {
+                self.status = format!("Could not open snapshot {}: {}", path.display(), e);
+                self.recent_snapshots.retain(|p| p != &path);
+                save_recent_snapshots(&self.recent_snapshots);
+            }
             Message::ShowWaitDialog => {
                 self.show_wait_dialog = true;
             }
@@ -399,11 +1400,47 @@ impl AppState {
                 self.aggregate_by_name();
                 self.mode = Mode::NewScreen;
             }
+            Message::ShowTree => {
+                self.build_tree();
+                self.mode = Mode::Tree;
+            }
+            Message::ToggleNode(path) => {
+                if !self.expanded.remove(&path) {
+                    self.expanded.insert(path);
+                }
+            }
+            Message::ShowTreemap => {
+                self.build_tree();
+                self.mode = Mode::Treemap;
+            }
+            Message::TreemapDrill(path) => {
+                self.treemap_root = Some(path);
+            }
+            Message::TreemapUp => {
+                let parent = self
+                    .treemap_root
+                    .as_deref()
+                    .and_then(|path| self.tree.find(path))
+                    .and_then(|id| self.tree.parent(id));
+                self.treemap_root = parent.map(|id| self.tree.node(id).path.clone());
+            }
+            Message::ShowSunburst => {
+                self.build_tree();
+                self.mode = Mode::Sunburst;
+            }
+            Message::SunburstZoom(path) => {
+                self.sunburst_root = Some(path);
+            }
+            Message::StartScan(_, _) => {}
+            Message::CloseRequested => return iced::exit(),
         }
         Task::none()
     }
     pub fn subscription(&self) -> Subscription<Message> {
-        Subscription::run(scanner_subscription)
+        Subscription::batch([
+            Subscription::run(scanner_subscription),
+            iced::window::close_requests().map(|_| Message::CloseRequested),
+        ])
     }
     pub fn view(&self) -> Element<Message> {
         let main_content = match self.mode {
@@ -412,7 +1449,7 @@ impl AppState {
                     self.header.clone(),
                     self.body.clone(),
                     &self.columns,
-                    &self.entries[..self.entries.len().min(self.settings.entries_visible)],
+                    self.visible_entries(),
                     Message::SyncHeader,
                 );
                 column![
@@ -425,6 +1462,15 @@ impl AppState {
                                 button("Settings")
                                     .style(button::text)
                                     .on_press(Message::GoToSettings),
+                                button("Tree View")
+                                    .style(button::text)
+                                    .on_press(Message::ShowTree),
+                                button("Treemap")
+                                    .style(button::text)
+                                    .on_press(Message::ShowTreemap),
+                                button("Sunburst")
+                                    .style(button::text)
+                                    .on_press(Message::ShowSunburst),
                             ];
                             #[cfg(feature = "view-by-folder-names")]
                             {
@@ -444,21 +1490,21 @@ impl AppState {
                     row![
                         button("Select Folder")
                             .style(styles::button_style::action_button)
-                            .on_press_maybe(if self.scanning {
+                            .on_press_maybe(if self.busy() {
                                 None
                             } else {
                                 Some(Message::OpenFolderDialog)
                             }),
                         button("Current User's Home")
                             .style(styles::button_style::action_button)
-                            .on_press_maybe(if self.scanning {
+                            .on_press_maybe(if self.busy() {
                                 None
                             } else {
                                 Some(Message::CurrentUser)
                             }),
                         button("All Users")
                             .style(styles::button_style::action_button)
-                            .on_press_maybe(if self.scanning {
+                            .on_press_maybe(if self.busy() {
                                 None
                             } else {
                                 Some(Message::AllUsers)
@@ -470,18 +1516,79 @@ impl AppState {
                             } else {
                                 None
                             }),
+                    ]
+                    .spacing(5),
+                    row![
                         button("Export as CSV")
                             .style(styles::button_style::action_button)
-                            .on_press_maybe(if self.scanning || self.entries.is_empty() {
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
                                 None
                             } else {
                                 Some(Message::ExportCsv)
                             }),
+                        button("Export as JSON")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
+                                None
+                            } else {
+                                Some(Message::ExportJson)
+                            }),
+                        button("Export for ncdu")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
+                                None
+                            } else {
+                                Some(Message::ExportNcdu)
+                            }),
+                        button("Export as HTML")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
+                                None
+                            } else {
+                                Some(Message::ExportHtml)
+                            }),
+                        button("Save Snapshot")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
+                                None
+                            } else {
+                                Some(Message::SaveSnapshot)
+                            }),
+                        button("Open Snapshot")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() {
+                                None
+                            } else {
+                                Some(Message::OpenSnapshotDialog)
+                            }),
+                        button("Compare with Snapshot")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() || self.entries.is_empty() {
+                                None
+                            } else {
+                                Some(Message::CompareWithSnapshot)
+                            }),
+                        button("Import ncdu Export")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() {
+                                None
+                            } else {
+                                Some(Message::ImportNcdu)
+                            }),
+                        button("Open Cleanup Plan")
+                            .style(styles::button_style::action_button)
+                            .on_press_maybe(if self.busy() {
+                                None
+                            } else {
+                                Some(Message::OpenPlan)
+                            }),
                     ]
                     .spacing(5),
-                    container(text(&self.status).size(20)),
-                    file_table,
                 ]
+                .push_maybe(self.recent_snapshots_row())
+                .push_maybe(self.basket_bar())
+                .push(self.status_bar())
+                .push(file_table)
                 .spacing(5)
                 .width(Length::Fill)
                 .align_x(Alignment::Center)
@@ -519,42 +1626,125 @@ impl AppState {
             .width(Length::Fill)
             .align_x(Alignment::Center)
             .into(),
-            Mode::Settings => column![
-                container(
-                    row![
-                        button("Home")
-                            .style(button::text)
-                            .on_press(Message::BackToMain),
-                        button("About")
-                            .style(button::text)
-                            .on_press(Message::ShowAbout),
-                    ]
-                    .spacing(5)
-                )
-                .align_right(Length::Fill)
-                .style(styles::layout_style::header_style),
+            Mode::Settings => {
+                let exclusion_rows = column(self.settings.exclusions.iter().enumerate().map(
+                    |(index, pattern)| {
+                        row![
+                            text(pattern).width(Length::Fill),
+                            button("Remove")
+                                .style(button::text)
+                                .on_press(Message::RemoveExclusion(index)),
+                        ]
+                        .align_y(Alignment::Center)
+                        .into()
+                    },
+                ))
+                .width(400);
                 column![
-                    text("Settings").size(50),
-                    row![
-                        text("Number of entries to show:"),
-                        text_input("", &self.settings.entries_visible.to_string())
-                            .on_input(Message::SetEntriesVisible),
+                    container(
+                        row![
+                            button("Home")
+                                .style(button::text)
+                                .on_press(Message::BackToMain),
+                            button("About")
+                                .style(button::text)
+                                .on_press(Message::ShowAbout),
+                        ]
+                        .spacing(5)
+                    )
+                    .align_right(Length::Fill)
+                    .style(styles::layout_style::header_style),
+                    column![
+                        text("Settings").size(50),
+                        row![
+                            text("Number of entries to show:"),
+                            text_input("", &self.settings.entries_visible.to_string())
+                                .on_input(Message::SetEntriesVisible)
+                                .on_submit(Message::SubmitSetting),
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        checkbox("Show Last Accessed Time", self.settings.show_last_accessed)
+                            .on_toggle(Message::SetShowLastAccessed),
+                        checkbox("Show Hidden Files", self.settings.show_hidden_files)
+                            .on_toggle(Message::SetShowHiddenFiles),
+                        row![
+                            text("Sizes:"),
+                            if SizeMode::DISTINCT {
+                                Element::from(pick_list(
+                                    SizeMode::ALL,
+                                    Some(self.settings.size_mode),
+                                    Message::SetSizeMode,
+                                ))
+                            } else {
+                                text("File lengths, Windows doesn't report the size on disk").into()
+                            },
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        row![
+                            text("Scanner threads:"),
+                            text_input("", &self.settings.scan_threads.to_string())
+                                .on_input(Message::SetScanThreads)
+                                .on_submit(Message::SubmitSetting),
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        row![
+                            text("Hard-linked files:"),
+                            pick_list(
+                                HardLinkMode::ALL,
+                                Some(self.settings.hard_links),
+                                Message::SetHardLinkMode
+                            ),
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        checkbox("Stay on one filesystem", self.settings.one_file_system)
+                            .on_toggle(Message::SetOneFileSystem),
+                        text("Exclude from scans:"),
+                        exclusion_rows,
+                        row![
+                            text_input("*.vmdk, node_modules, re:^/proc/", &self.new_exclusion)
+                                .on_input(Message::SetNewExclusion)
+                                .on_submit(Message::AddExclusion)
+                                .width(325),
+                            button("Add")
+                                .style(styles::button_style::action_button)
+                                .on_press(Message::AddExclusion),
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        text(self.exclusion_error.as_deref().unwrap_or_default()),
+                        checkbox(
+                            "List excluded folders in results",
+                            self.settings.show_excluded
+                        )
+                        .on_toggle(Message::SetShowExcluded),
+                        checkbox(
+                            "Nest folders under their parents in JSON exports",
+                            self.settings.nested_json
+                        )
+                        .on_toggle(Message::SetNestedJson),
+                        row![
+                            button("Reset to Defaults")
+                                .style(styles::button_style::action_button)
+                                .on_press(Message::ResetSettings),
+                            button("Open Deletion Log")
+                                .style(styles::button_style::action_button)
+                                .on_press(Message::OpenDeletionLog),
+                        ]
+                        .spacing(10),
                     ]
-                    .spacing(10)
-                    .align_y(Alignment::Center),
-                    checkbox("Show Last Accessed Time", self.settings.show_last_accessed)
-                        .on_toggle(Message::SetShowLastAccessed),
-                    checkbox("Show Hidden Files", self.settings.show_hidden_files)
-                        .on_toggle(Message::SetShowHiddenFiles),
+                    .padding(10)
+                    .width(Length::Fill)
+                    .align_x(Alignment::Center)
                 ]
-                .padding(10)
+                .spacing(5)
                 .width(Length::Fill)
                 .align_x(Alignment::Center)
-            ]
-            .spacing(5)
-            .width(Length::Fill)
-            .align_x(Alignment::Center)
-            .into(),
+                .into()
+            }
             Mode::NewScreen => {
                 let aggregated_table = table(
                     self.header.clone(),
@@ -592,49 +1782,908 @@ impl AppState {
                 .align_x(Alignment::Center)
                 .into()
             }
-        };
-
-        if self.show_wait_dialog {
-            stack![
-                main_content,
-                container(
+            Mode::Tree => {
+                let mut rows = Vec::new();
+                if !self.tree.is_empty() {
+                    let root = self.tree.root();
+                    self.tree_rows(root, 0, self.tree.node(root).size, &mut rows);
+                }
+                column![
                     container(
-                        column![
-                            text("Please wait").size(24),
-                            text("Scanning is currently in progress.").size(16),
-                            button("OK")
-                                .on_press(Message::CloseWaitDialog)
-                                .style(styles::button_style::action_button),
+                        row![
+                            button("Home")
+                                .style(button::text)
+                                .on_press(Message::BackToMain),
+                            button("About")
+                                .style(button::text)
+                                .on_press(Message::ShowAbout),
+                            button("Settings")
+                                .style(button::text)
+                                .on_press(Message::GoToSettings),
                         ]
-                        .spacing(15)
-                        .align_x(Alignment::Center)
+                        .spacing(5)
                     )
-                    .padding(30)
-                    .style(container::rounded_box)
-                )
+                    .align_right(Length::Fill)
+                    .style(styles::layout_style::header_style),
+                    text("FindBigFolders").size(50),
+                    container(text(&self.status).size(20)),
+                    scrollable(column(rows).spacing(2).padding(10)).height(Length::Fill),
+                ]
+                .spacing(5)
                 .width(Length::Fill)
-                .height(Length::Fill)
                 .align_x(Alignment::Center)
-                .align_y(Alignment::Center)
-                .style(|_theme: &Theme| container::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgba(
-                        0.0, 0.0, 0.0, 0.7
-                    ))),
-                    ..Default::default()
-                })
+                .into()
+            }
+            Mode::Diff => column![
+                container(
+                    row![
+                        button("Home")
+                            .style(button::text)
+                            .on_press(Message::BackToMain),
+                        button("About")
+                            .style(button::text)
+                            .on_press(Message::ShowAbout),
+                        button("Settings")
+                            .style(button::text)
+                            .on_press(Message::GoToSettings),
+                    ]
+                    .spacing(5)
+                )
+                .align_right(Length::Fill)
+                .style(styles::layout_style::header_style),
+                text("FindBigFolders").size(50),
+                container(text(self.diff_summary()).size(20)),
+                row![
+                    text("Sort by:"),
+                    pick_list(DiffSort::ALL, Some(self.diff_sort), Message::SetDiffSort),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+                scrollable(column(self.diff_rows()).spacing(2).padding(10)).height(Length::Fill),
             ]
-            .into()
+            .spacing(5)
+            .width(Length::Fill)
+            .align_x(Alignment::Center)
+            .into(),
+            Mode::Treemap => {
+                let content: Element<Message> = if self.tree.is_empty() {
+                    container(text("Scan a folder to see its treemap").size(20)).into()
+                } else {
+                    let root = self
+                        .treemap_root
+                        .as_deref()
+                        .and_then(|path| self.tree.find(path))
+                        .unwrap_or(self.tree.root());
+                    let node = self.tree.node(root);
+                    column![
+                        row![
+                            button("Up")
+                                .style(styles::button_style::action_button)
+                                .on_press_maybe(self.tree.parent(root).map(|_| Message::TreemapUp)),
+                            text(format!(
+                                "{} ({})",
+                                node.path.display(),
+                                format_size(node.size)
+                            )),
+                        ]
+                        .spacing(10)
+                        .align_y(Alignment::Center),
+                        canvas(Treemap {
+                            tree: &self.tree,
+                            root,
+                        })
+                        .width(Length::Fill)
+                        .height(Length::Fill),
+                    ]
+                    .spacing(5)
+                    .padding(10)
+                    .into()
+                };
+                column![
+                    container(
+                        row![
+                            button("Home")
+                                .style(button::text)
+                                .on_press(Message::BackToMain),
+                            button("About")
+                                .style(button::text)
+                                .on_press(Message::ShowAbout),
+                            button("Settings")
+                                .style(button::text)
+                                .on_press(Message::GoToSettings),
+                        ]
+                        .spacing(5)
+                    )
+                    .align_right(Length::Fill)
+                    .style(styles::layout_style::header_style),
+                    text("FindBigFolders").size(50),
+                    content,
+                ]
+                .spacing(5)
+                .width(Length::Fill)
+                .align_x(Alignment::Center)
+                .into()
+            }
+            Mode::Sunburst => {
+                let content: Element<Message> = if self.tree.is_empty() {
+                    container(text("Scan a folder to see its sunburst chart").size(20)).into()
+                } else {
+                    let root = self
+                        .sunburst_root
+                        .as_deref()
+                        .and_then(|path| self.tree.find(path))
+                        .unwrap_or(self.tree.root());
+                    let mut crumbs = vec![root];
+                    while let Some(parent) = self.tree.parent(*crumbs.last().unwrap()) {
+                        crumbs.push(parent);
+                    }
+                    let breadcrumb = row(crumbs.into_iter().rev().map(|id| {
+                        let node = self.tree.node(id);
+                        button(text(&node.name))
+                            .style(button::text)
+                            .on_press_maybe(if id == root {
+                                None
+                            } else {
+                                Some(Message::SunburstZoom(node.path.clone()))
+                            })
+                            .into()
+                    }))
+                    .spacing(2)
+                    .align_y(Alignment::Center);
+                    column![
+                        row![breadcrumb, text(format_size(self.tree.node(root).size)),]
+                            .spacing(10)
+                            .align_y(Alignment::Center),
+                        canvas(Sunburst {
+                            tree: &self.tree,
+                            root,
+                        })
+                        .width(Length::Fill)
+                        .height(Length::Fill),
+                    ]
+                    .spacing(5)
+                    .padding(10)
+                    .into()
+                };
+                column![
+                    container(
+                        row![
+                            button("Home")
+                                .style(button::text)
+                                .on_press(Message::BackToMain),
+                            button("About")
+                                .style(button::text)
+                                .on_press(Message::ShowAbout),
+                            button("Settings")
+                                .style(button::text)
+                                .on_press(Message::GoToSettings),
+                        ]
+                        .spacing(5)
+                    )
+                    .align_right(Length::Fill)
+                    .style(styles::layout_style::header_style),
+                    text("FindBigFolders").size(50),
+                    content,
+                ]
+                .spacing(5)
+                .width(Length::Fill)
+                .align_x(Alignment::Center)
+                .into()
+            }
+        };
+
+        if self.show_wait_dialog {
+            dialog(
+                main_content,
+                column![
+                    text("Please wait").size(24),
+                    text(if self.scanning {
+                        "Scanning is currently in progress."
+                    } else {
+                        "A cleanup is currently in progress."
+                    })
+                    .size(16),
+                    button("OK")
+                        .on_press(Message::CloseWaitDialog)
+                        .style(styles::button_style::action_button),
+                ]
+                .spacing(15)
+                .align_x(Alignment::Center)
+                .into(),
+            )
+        } else if self.show_csv_dialog {
+            dialog(main_content, self.csv_dialog())
+        } else if let Some(path) = &self.pending_trash {
+            dialog(main_content, self.trash_dialog(path))
+        } else if let Some(path) = &self.pending_delete {
+            dialog(main_content, self.delete_dialog(path))
+        } else if let Some(action) = &self.pending_batch {
+            dialog(
+                main_content,
+                self.batch_dialog(action, self.basket_folders(), None),
+            )
+        } else if let Some(plan) = &self.pending_plan {
+            dialog(
+                main_content,
+                self.batch_dialog(&plan.action, plan.folders.clone(), Some(plan)),
+            )
         } else {
             main_content
         }
     }
 }
 
+fn dialog<'a>(
+    main_content: Element<'a, Message>,
+    content: Element<'a, Message>,
+) -> Element<'a, Message> {
+    stack![
+        main_content,
+        container(container(content).padding(30).style(container::rounded_box))
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .align_x(Alignment::Center)
+            .align_y(Alignment::Center)
+            .style(|_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(iced::Color::from_rgba(
+                    0.0, 0.0, 0.0, 0.7
+                ))),
+                ..Default::default()
+            })
+    ]
+    .into()
+}
+
 impl AppState {
+    fn status_bar(&self) -> Element<'_, Message> {
+        let status = container(text(&self.status).size(20));
+        match &self.export_failure {
+            Some(failure) => column![
+                status,
+                row![
+                    text(format!(
+                        "Could not save {}: {}",
+                        failure.path.display(),
+                        failure.error
+                    ))
+                    .style(text::danger),
+                    button("Retry")
+                        .style(styles::button_style::action_button)
+                        .on_press(Message::RetryExport),
+                    button("Dismiss")
+                        .style(button::text)
+                        .on_press(Message::DismissExportError),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Center),
+            ]
+            .spacing(5)
+            .align_x(Alignment::Center)
+            .into(),
+            None => status.into(),
+        }
+    }
+
+    /// Exports to `path`, or asks where to save when it is `None`.
+    fn export_csv(&self, path: Option<PathBuf>) -> Task<Message> {
+        let Some(root) = self.scan_root.clone() else {
+            return Task::none();
+        };
+        let entries = self.entries.clone();
+        let options = self.scan_options.clone();
+        let csv_options = self.settings.csv_options;
+        let scanned_here = self.loaded_from.is_none();
+        Task::perform(
+            async move {
+                let path = match path {
+                    Some(path) => path,
+                    None => match save_path("CSV", "csv", Local::now()).await {
+                        Some(path) => path,
+                        None => return Ok(()),
+                    },
+                };
+                write_csv_file(
+                    &path,
+                    &root,
+                    &entries,
+                    options.as_ref(),
+                    &csv_options,
+                    scanned_here,
+                )
+                .map_err(|e| ExportFailure {
+                    format: ExportFormat::Csv,
+                    path,
+                    error: e.to_string(),
+                })
+            },
+            Message::CsvExported,
+        )
+    }
+
+    /// Exports to `path`, or asks where to save when it is `None`.
+    fn export_json(&self, path: Option<PathBuf>) -> Task<Message> {
+        let (Some(root), Some(scanned_at)) = (self.scan_root.clone(), self.scanned_at) else {
+            return Task::none();
+        };
+        let options = self.scan_options.clone();
+        let entries = self.entries.clone();
+        let nested = self.settings.nested_json;
+        let scanned_here = self.loaded_from.is_none();
+        Task::perform(
+            async move {
+                let path = match path {
+                    Some(path) => path,
+                    None => match save_path("JSON", "json", scanned_at).await {
+                        Some(path) => path,
+                        None => return Ok(()),
+                    },
+                };
+                write_json_file(
+                    &path,
+                    &root,
+                    scanned_at,
+                    &entries,
+                    options.as_ref(),
+                    nested,
+                    scanned_here,
+                )
+                .map_err(|e| ExportFailure {
+                    format: ExportFormat::Json,
+                    path,
+                    error: e.to_string(),
+                })
+            },
+            Message::JsonExported,
+        )
+    }
+
+    /// Exports to `path`, or asks where to save when it is `None`.
+    fn export_ncdu(&self, path: Option<PathBuf>) -> Task<Message> {
+        let (Some(root), Some(scanned_at)) = (self.scan_root.clone(), self.scanned_at) else {
+            return Task::none();
+        };
+        let entries = self.entries.clone();
+        Task::perform(
+            async move {
+                let path = match path {
+                    Some(path) => path,
+                    None => match save_path("ncdu export", "json", scanned_at).await {
+                        Some(path) => path,
+                        None => return Ok(()),
+                    },
+                };
+                write_ncdu_file(&path, &root, scanned_at, &entries).map_err(|e| ExportFailure {
+                    format: ExportFormat::Ncdu,
+                    path,
+                    error: e.to_string(),
+                })
+            },
+            Message::NcduExported,
+        )
+    }
+
+    /// Exports to `path`, or asks where to save when it is `None`.
+    fn export_html(&self, path: Option<PathBuf>) -> Task<Message> {
+        let (Some(root), Some(scanned_at)) = (self.scan_root.clone(), self.scanned_at) else {
+            return Task::none();
+        };
+        let options = self.scan_options.clone();
+        let entries = self.entries.clone();
+        let size_mode = self.settings.size_mode;
+        let top = self.settings.entries_visible;
+        Task::perform(
+            async move {
+                let path = match path {
+                    Some(path) => path,
+                    None => match save_path("HTML", "html", scanned_at).await {
+                        Some(path) => path,
+                        None => return Ok(()),
+                    },
+                };
+                std::fs::File::create(&path)
+                    .and_then(|file| {
+                        export::write_html(
+                            std::io::BufWriter::new(file),
+                            &root,
+                            scanned_at,
+                            &entries,
+                            options.as_ref(),
+                            size_mode,
+                            top,
+                        )
+                    })
+                    .map_err(|e| ExportFailure {
+                        format: ExportFormat::Html,
+                        path,
+                        error: e.to_string(),
+                    })
+            },
+            Message::HtmlExported,
+        )
+    }
+
+    fn recent_snapshots_row(&self) -> Option<Element<'_, Message>> {
+        if self.recent_snapshots.is_empty() {
+            return None;
+        }
+        let mut recent = row![text("Recent snapshots:")]
+            .spacing(5)
+            .align_y(Alignment::Center);
+        for path in &self.recent_snapshots {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string());
+            recent = recent.push(button(text(name)).style(button::text).on_press_maybe(
+                if self.busy() {
+                    None
+                } else {
+                    Some(Message::SnapshotSelected(Some(path.clone())))
+                },
+            ));
+        }
+        Some(recent.into())
+    }
+
+    /// Saves to `path`, or asks where to save when it is `None`.
+    fn save_snapshot(&self, path: Option<PathBuf>) -> Task<Message> {
+        let (Some(root), Some(scanned_at)) = (self.scan_root.clone(), self.scanned_at) else {
+            return Task::none();
+        };
+        let options = self.scan_options.clone();
+        let entries = self.entries.clone();
+        let skipped_mounts = self.skipped_mounts.clone();
+        let scanned_here = self.loaded_from.is_none();
+        Task::perform(
+            async move {
+                let path = match path {
+                    Some(path) => path,
+                    None => match save_path("Snapshot", snapshot::EXTENSION, scanned_at).await {
+                        Some(path) => path,
+                        None => return Ok(None),
+                    },
+                };
+                let target = path.clone();
+                let result = blocking(move || {
+                    let mut snapshot =
+                        Snapshot::new(root, scanned_at, options, skipped_mounts, entries);
+                    // Only the visible rows have their access time filled in,
+                    // so look up the rest now and keep reopened snapshots off
+                    // the disk. Loaded scans have nothing to look up here.
+                    if scanned_here {
+                        for entry in &mut snapshot.entries {
+                            if entry.accessed.is_none() {
+                                entry.accessed = std::fs::metadata(&entry.file)
+                                    .and_then(|m| m.accessed())
+                                    .ok()
+                                    .map(DateTime::<Local>::from);
+                            }
+                        }
+                    }
+                    snapshot.save(&target).map_err(|e| e.to_string())
+                })
+                .await;
+                match result {
+                    Ok(()) => Ok(Some(path)),
+                    Err(error) => Err(ExportFailure {
+                        format: ExportFormat::Snapshot,
+                        path,
+                        error,
+                    }),
+                }
+            },
+            Message::SnapshotSaved,
+        )
+    }
+
+    fn remember_snapshot(&mut self, path: PathBuf) {
+        self.recent_snapshots.retain(|p| p != &path);
+        self.recent_snapshots.insert(0, path);
+        self.recent_snapshots.truncate(MAX_RECENT_SNAPSHOTS);
+        save_recent_snapshots(&self.recent_snapshots);
+    }
+
+    fn trash_dialog<'a>(&'a self, path: &'a str) -> Element<'a, Message> {
+        let size = self
+            .entries
+            .iter()
+            .find(|entry| entry.file == path)
+            .map(|entry| entry.size)
+            .unwrap_or(0);
+        column![
+            text("Move to Trash").size(24),
+            text(path),
+            text(format!(
+                "This frees {} once the trash is emptied.",
+                format_size(size)
+            )),
+            row![
+                button("Cancel")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::CancelTrash),
+                button("Move to Trash")
+                    .style(styles::button_style::stop_button)
+                    .on_press(Message::MoveToTrash),
+            ]
+            .spacing(10),
+        ]
+        .spacing(10)
+        .align_x(Alignment::Center)
+        .into()
+    }
+
+    /// Permanent deletion of a batch needs the confirmation word typed in.
+    fn delete_confirmed(&self, action: &Action) -> bool {
+        *action != Action::Delete || self.delete_confirmation == BATCH_CONFIRMATION
+    }
+
+    fn delete_dialog<'a>(&'a self, path: &'a str) -> Element<'a, Message> {
+        let name = folder_name(path);
+        let size = self
+            .entries
+            .iter()
+            .find(|entry| entry.file == path)
+            .map(|entry| entry.size)
+            .unwrap_or(0);
+        column![
+            text("Delete Permanently").size(24),
+            text(path),
+            text(format!(
+                "This deletes {} for good, it can't be restored from the trash.",
+                format_size(size)
+            )),
+            text(format!("Type \"{}\" to confirm:", name)),
+            text_input(&name, &self.delete_confirmation)
+                .on_input(Message::SetDeleteConfirmation)
+                .width(325),
+            row![
+                button("Cancel")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::CancelDelete),
+                button("Delete Permanently")
+                    .style(styles::button_style::stop_button)
+                    .on_press_maybe(if !name.is_empty() && self.delete_confirmation == name {
+                        Some(Message::DeletePermanently)
+                    } else {
+                        None
+                    }),
+            ]
+            .spacing(10),
+        ]
+        .spacing(10)
+        .align_x(Alignment::Center)
+        .into()
+    }
+
+    /// A scan or cleanup is running, so nothing else may start.
+    fn busy(&self) -> bool {
+        self.scanning || self.applying
+    }
+
+    /// The folders in the basket, leaving out any that sit inside another
+    /// selected folder.
+    fn basket_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.basket.keys().map(PathBuf::from).collect();
+        paths.sort();
+        cleanup::outermost(&paths)
+    }
+
+    /// The basket's folders with the space each would free.
+    fn basket_folders(&self) -> Vec<PlannedFolder> {
+        self.basket_paths()
+            .into_iter()
+            .map(|path| {
+                let size = self.basket[path.to_string_lossy().as_ref()];
+                PlannedFolder { path, size }
+            })
+            .collect()
+    }
+
+    fn sync_basket(&mut self) {
+        for column in &mut self.columns {
+            if column.kind == FileColumnKind::Select {
+                column.basket = self.basket.keys().cloned().collect();
+            }
+        }
+    }
+
+    fn basket_bar(&self) -> Option<Element<'_, Message>> {
+        if self.basket.is_empty() {
+            return None;
+        }
+        let idle = |message: Message| if self.busy() { None } else { Some(message) };
+        let folders = self.basket_folders();
+        Some(
+            row![
+                text(format!(
+                    "Cleanup basket: {} folders, {} reclaimable",
+                    folders.len(),
+                    format_size(folders.iter().map(|folder| folder.size).sum())
+                )),
+                button("Move to Trash")
+                    .style(styles::button_style::action_button)
+                    .on_press_maybe(idle(Message::ConfirmBatch(Action::Trash))),
+                button("Delete Permanently")
+                    .style(styles::button_style::stop_button)
+                    .on_press_maybe(idle(Message::ConfirmBatch(Action::Delete))),
+                button("Move to…")
+                    .style(styles::button_style::action_button)
+                    .on_press_maybe(idle(Message::PickMoveDestination)),
+                button("Compress…")
+                    .style(styles::button_style::action_button)
+                    .on_press_maybe(idle(Message::PickArchive)),
+                button("Clear")
+                    .style(button::text)
+                    .on_press(Message::ClearBasket),
+            ]
+            .spacing(5)
+            .align_y(Alignment::Center)
+            .into(),
+        )
+    }
+
+    /// Confirmation for a batch action on the basket, or for a loaded
+    /// `plan` which can't be saved again but runs the same way.
+    fn batch_dialog<'a>(
+        &'a self,
+        action: &'a Action,
+        folders: Vec<PlannedFolder>,
+        plan: Option<&'a Plan>,
+    ) -> Element<'a, Message> {
+        const MAX_LISTED: usize = 8;
+        let count = folders.len();
+        let size = format_size(folders.iter().map(|folder| folder.size).sum());
+        let (title, description) = match action {
+            Action::Trash => (
+                "Move to Trash",
+                format!(
+                    "Move {} folders to the trash, freeing {} once it is emptied.",
+                    count, size
+                ),
+            ),
+            Action::Delete => (
+                "Delete Permanently",
+                format!(
+                    "Delete {} folders for good, freeing {}. They can't be restored from the trash.",
+                    count, size
+                ),
+            ),
+            Action::Move(destination) => (
+                "Move Folders",
+                format!(
+                    "Move {} folders ({}) into {}.",
+                    count,
+                    size,
+                    destination.display()
+                ),
+            ),
+            Action::Compress(archive) => (
+                "Compress Folders",
+                format!(
+                    "Pack {} folders ({}) into {} and remove the originals.",
+                    count,
+                    size,
+                    archive.display()
+                ),
+            ),
+        };
+        let mut listed = column(folders.iter().take(MAX_LISTED).map(|folder| {
+            text(format!(
+                "{} ({})",
+                folder.path.display(),
+                format_size(folder.size)
+            ))
+            .into()
+        }))
+        .spacing(2);
+        if count > MAX_LISTED {
+            listed = listed.push(text(format!("and {} more", count - MAX_LISTED)));
+        }
+        let mut content = column![text(title).size(24)]
+            .spacing(10)
+            .align_x(Alignment::Center);
+        if let Some(plan) = plan {
+            content = content.push(text(format!(
+                "Planned on {} from a scan of {}",
+                plan.created_at.format("%Y-%m-%d %H:%M"),
+                plan.root.display()
+            )));
+        }
+        content = content.push(listed).push(text(description));
+        if *action == Action::Delete {
+            content = content
+                .push(text(format!("Type \"{}\" to confirm:", BATCH_CONFIRMATION)))
+                .push(
+                    text_input(BATCH_CONFIRMATION, &self.delete_confirmation)
+                        .on_input(Message::SetDeleteConfirmation)
+                        .width(325),
+                );
+        }
+        let confirmed = self.delete_confirmed(action);
+        let buttons = match plan {
+            Some(_) => row![
+                button("Cancel")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::CancelPlan),
+                button("Run Plan")
+                    .style(styles::button_style::stop_button)
+                    .on_press_maybe(confirmed.then_some(Message::RunPlan)),
+            ],
+            None => row![
+                button("Cancel")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::CancelBatch),
+                button("Save as Plan…")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::SavePlan),
+                button(title)
+                    .style(styles::button_style::stop_button)
+                    .on_press_maybe(confirmed.then_some(Message::RunBatch)),
+            ],
+        };
+        content.push(buttons.spacing(10)).into()
+    }
+
+    fn is_protected(&self, path: &str) -> bool {
+        match &self.scan_root {
+            Some(root) => cleanup::is_protected(Path::new(path), root),
+            None => true,
+        }
+    }
+
+    /// Updates the results after a folder has gone from disk, without
+    /// rescanning.
+    fn remove_folder(&mut self, path: &Path) -> Option<FileEntry> {
+        let removed = scanner::remove_folder(&mut self.entries, path)?;
+        self.expanded.retain(|p| !p.starts_with(path));
+        self.basket.retain(|p, _| !Path::new(p).starts_with(path));
+        for (p, size) in self.basket.iter_mut() {
+            if path.starts_with(p) {
+                *size = size.saturating_sub(removed.size);
+            }
+        }
+        self.sync_basket();
+        if self
+            .treemap_root
+            .as_ref()
+            .is_some_and(|p| p.starts_with(path))
+        {
+            self.treemap_root = None;
+        }
+        if self
+            .sunburst_root
+            .as_ref()
+            .is_some_and(|p| p.starts_with(path))
+        {
+            self.sunburst_root = None;
+        }
+        self.bake_entries();
+        self.build_tree();
+        self.build_diff();
+        self.aggregate_by_name();
+        Some(removed)
+    }
+
+    fn csv_dialog(&self) -> Element<'_, Message> {
+        let options = self.settings.csv_options;
+        column![
+            text("Export as CSV").size(24),
+            checkbox("Raw byte sizes instead of KB/MB/GB", options.raw_sizes).on_toggle(
+                move |raw_sizes| Message::SetCsvOptions(CsvOptions {
+                    raw_sizes,
+                    ..options
+                })
+            ),
+            checkbox("File and subfolder counts", options.counts).on_toggle(move |counts| {
+                Message::SetCsvOptions(CsvOptions { counts, ..options })
+            }),
+            checkbox("Last accessed time", options.accessed).on_toggle(move |accessed| {
+                Message::SetCsvOptions(CsvOptions {
+                    accessed,
+                    ..options
+                })
+            }),
+            checkbox("Last modified time", options.modified).on_toggle(move |modified| {
+                Message::SetCsvOptions(CsvOptions {
+                    modified,
+                    ..options
+                })
+            }),
+            checkbox("Depth below the scanned folder", options.depth).on_toggle(move |depth| {
+                Message::SetCsvOptions(CsvOptions { depth, ..options })
+            }),
+            checkbox("Owner", options.owner).on_toggle(move |owner| {
+                Message::SetCsvOptions(CsvOptions { owner, ..options })
+            }),
+            checkbox("Keep empty folders", options.keep_empty).on_toggle(move |keep_empty| {
+                Message::SetCsvOptions(CsvOptions {
+                    keep_empty,
+                    ..options
+                })
+            }),
+            row![
+                text("Delimiter"),
+                pick_list(
+                    CsvDelimiter::ALL,
+                    Some(options.delimiter),
+                    move |delimiter| Message::SetCsvOptions(CsvOptions {
+                        delimiter,
+                        ..options
+                    })
+                ),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+            row![
+                button("Cancel")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::CloseCsvDialog),
+                button("Save…")
+                    .style(styles::button_style::action_button)
+                    .on_press(Message::SaveCsv),
+            ]
+            .spacing(10),
+        ]
+        .spacing(10)
+        .into()
+    }
+
+    fn clear_results(&mut self) {
+        self.entries.clear();
+        self.excluded_count = 0;
+        self.skipped_mounts.clear();
+        self.expanded.clear();
+        self.treemap_root = None;
+        self.sunburst_root = None;
+        self.tree = ScanTree::default();
+        self.loaded_from = None;
+        self.baseline = None;
+        self.diff.clear();
+        self.basket.clear();
+        self.sync_basket();
+    }
+
+    fn start_scan(&mut self, root: PathBuf) {
+        self.clear_results();
+        self.scan_root = Some(root.clone());
+        self.scanned_at = Some(Local::now());
+        if let Some(tx) = &mut self.search_tx {
+            let options = self.settings.scan_options();
+            self.scanning = true;
+            self.scan_options = Some(options.clone());
+            let _ = tx.try_send(Message::StartScan(root, options));
+        }
+    }
+
+    fn visible_entries(&self) -> &[FileEntry] {
+        &self.entries[..self.entries.len().min(self.settings.entries_visible)]
+    }
+
     fn bake_entries(&mut self) {
-        self.entries.sort_by(|a, b| b.size.cmp(&a.size));
+        let size_mode = self.settings.size_mode;
+        match self.sort_by {
+            FileColumnKind::Files => self.entries.sort_by(|a, b| b.files.cmp(&a.files)),
+            FileColumnKind::Folders => self.entries.sort_by(|a, b| b.dirs.cmp(&a.dirs)),
+            _ => self
+                .entries
+                .sort_by(|a, b| b.size_for(size_mode).cmp(&a.size_for(size_mode))),
+        }
+        // Excluded folders have no size, so they would always sort below the
+        // cutoff. List them right after the biggest ones instead, within the
+        // rows shown but leaving at least half of them to the biggest ones.
+        let visible = self.settings.entries_visible;
+        let (excluded, mut entries): (Vec<_>, Vec<_>) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|entry| entry.excluded);
+        self.excluded_count = excluded.len();
+        let biggest = entries
+            .len()
+            .min(visible - self.excluded_count.min(visible / 2));
+        entries.splice(biggest..biggest, excluded);
+        self.entries = entries;
 
-        if self.settings.show_last_accessed {
+        // Imported and reopened scans may describe another machine's folders.
+        if self.settings.show_last_accessed && self.loaded_from.is_none() {
             for entry in self.entries.iter_mut().take(self.settings.entries_visible) {
                 if entry.accessed.is_none() {
                     if let Ok(metadata) = std::fs::metadata(&entry.file) {
@@ -648,193 +2697,357 @@ impl AppState {
         self.status = format!(
             "Scanned {} folders, showing the {} biggest ones",
             self.entries.len(),
-            self.settings.entries_visible
+            if self.excluded_count > 0 {
+                biggest
+            } else {
+                visible
+            }
         );
+        if self.excluded_count > 0 {
+            self.status += &format!(
+                " and {} of {} excluded ones",
+                self.excluded_count.min(visible - biggest),
+                self.excluded_count
+            );
+        }
+        if !self.skipped_mounts.is_empty() {
+            const MAX_LISTED: usize = 3;
+            let mut listed =
+                self.skipped_mounts[..self.skipped_mounts.len().min(MAX_LISTED)].join(", ");
+            if self.skipped_mounts.len() > MAX_LISTED {
+                listed += &format!(" and {} more", self.skipped_mounts.len() - MAX_LISTED);
+            }
+            self.status += &format!(" (skipped mount points: {})", listed);
+        }
+        if let (Some(path), Some(scanned_at)) = (&self.loaded_from, self.scanned_at) {
+            self.status += &format!(
+                " from {}, scanned {}",
+                path.file_name().unwrap_or_default().to_string_lossy(),
+                scanned_at.format("%Y-%m-%d %H:%M")
+            );
+        }
     }
 
-    fn aggregate_by_name(&mut self) {
-        use std::collections::HashMap;
+    fn build_tree(&mut self) {
+        if let Some(root) = &self.scan_root {
+            self.tree = ScanTree::build(root, &self.entries, self.settings.size_mode);
+        }
+    }
 
-        let mut name_map: HashMap<String, (u64, usize)> = HashMap::new();
+    fn build_diff(&mut self) {
+        if let Some(baseline) = &self.baseline {
+            self.diff = diff::compare(&baseline.entries, &self.entries, self.settings.size_mode);
+            diff::sort(&mut self.diff, self.diff_sort);
+        }
+    }
 
-        for entry in &self.entries {
-            if let Some(name) = std::path::Path::new(&entry.file)
-                .file_name()
-                .and_then(|n| n.to_str())
-            {
-                let (total_size, count) = name_map.entry(name.to_string()).or_insert((0, 0));
-                *total_size += entry.size;
-                *count += 1;
-            }
+    fn diff_summary(&self) -> String {
+        let Some(baseline) = &self.baseline else {
+            return String::new();
+        };
+        let count = |change: Change| self.diff.iter().filter(|d| d.change == change).count();
+        // Only the folders directly below the root add up to the total.
+        let total = |entries: &[FileEntry]| {
+            entries
+                .iter()
+                .filter(|entry| Path::new(&entry.file).parent() == Some(baseline.root.as_path()))
+                .map(|entry| entry.size_for(self.settings.size_mode) as i64)
+                .sum::<i64>()
+        };
+        let net = total(&self.entries) - total(&baseline.entries);
+        format!(
+            "Since {}: {} added, {} removed, {} grown, {} shrunk, {} overall",
+            baseline.scanned_at.format("%Y-%m-%d %H:%M"),
+            count(Change::Added),
+            count(Change::Removed),
+            count(Change::Grown),
+            count(Change::Shrunk),
+            format_delta(net)
+        )
+    }
+
+    fn diff_rows(&self) -> Vec<Element<'_, Message>> {
+        let mut rows: Vec<Element<Message>> = vec![row![
+            text("").width(80),
+            text("Folder").width(400),
+            text("Before").width(100),
+            text("After").width(100),
+            text("Change").width(100),
+            text("%").width(80),
+        ]
+        .spacing(5)
+        .into()];
+
+        let shown = self.diff.len().min(self.settings.entries_visible);
+        for folder in &self.diff[..shown] {
+            let size = |size: Option<u64>| size.map(format_size).unwrap_or_default();
+            rows.push(
+                row![
+                    text(folder.change.to_string())
+                        .width(80)
+                        .style(match folder.change {
+                            Change::Added | Change::Grown => text::danger,
+                            Change::Removed | Change::Shrunk => text::success,
+                        }),
+                    text(&folder.path).width(400),
+                    text(size(folder.before)).width(100),
+                    text(size(folder.after)).width(100),
+                    text(format_delta(folder.delta())).width(100),
+                    text(
+                        folder
+                            .percent()
+                            .map(|percent| format!("{:+.1}%", percent))
+                            .unwrap_or_default()
+                    )
+                    .width(80),
+                ]
+                .spacing(5)
+                .align_y(Alignment::Center)
+                .into(),
+            );
         }
+        if self.diff.len() > shown {
+            rows.push(text(format!("… {} more folders", self.diff.len() - shown)).into());
+        }
+        rows
+    }
 
-        self.aggregated_entries = name_map
-            .into_iter()
-            .map(|(name, (total_size, count))| AggregatedEntry {
-                name,
-                total_size,
-                count,
-            })
-            .collect();
+    fn tree_rows<'a>(
+        &'a self,
+        id: usize,
+        depth: usize,
+        parent_size: u64,
+        rows: &mut Vec<Element<'a, Message>>,
+    ) {
+        const INDENT: f32 = 20.0;
+
+        let node = self.tree.node(id);
+        let is_root = id == self.tree.root();
+        let expanded = is_root || self.expanded.contains(&node.path);
+        let percent = if parent_size > 0 {
+            node.size as f32 * 100.0 / parent_size as f32
+        } else {
+            0.0
+        };
 
-        self.aggregated_entries
-            .sort_by(|a, b| b.total_size.cmp(&a.total_size));
+        let toggle: Element<Message> = if is_root || node.children.is_empty() {
+            Space::with_width(INDENT).into()
+        } else {
+            button(text(if expanded { "▾" } else { "▸" }))
+                .style(button::text)
+                .padding(0)
+                .width(INDENT)
+                .on_press(Message::ToggleNode(node.path.clone()))
+                .into()
+        };
+        rows.push(
+            row![
+                Space::with_width(depth as f32 * INDENT),
+                toggle,
+                text(&node.name).width(400),
+                text(format_size(node.size)).width(100),
+                progress_bar(0.0..=100.0, percent).width(150).height(10),
+                text(format!("{:.1}%", percent)).width(60),
+            ]
+            .spacing(5)
+            .align_y(Alignment::Center)
+            .into(),
+        );
+
+        if expanded {
+            let shown = node.children.len().min(self.settings.entries_visible);
+            for &child in &node.children[..shown] {
+                self.tree_rows(child, depth + 1, node.size, rows);
+            }
+            if node.children.len() > shown {
+                rows.push(
+                    row![
+                        Space::with_width((depth + 2) as f32 * INDENT),
+                        text(format!("… {} more folders", node.children.len() - shown)),
+                    ]
+                    .into(),
+                );
+            }
+        }
     }
-}
 
-#[cfg(unix)]
-fn get_allocated_size(path: &Path) -> u64 {
-    use std::os::unix::fs::MetadataExt;
-    if let Ok(meta) = path.metadata() {
-        let blocks = meta.blocks();
-        blocks * 512
-    } else {
-        0
+    fn aggregate_by_name(&mut self) {
+        self.aggregated_entries = aggregate_by_name(&self.entries, self.settings.size_mode);
     }
 }
 
-#[cfg(windows)]
-fn get_allocated_size(path: &Path) -> u64 {
-    use std::os::windows::fs::MetadataExt;
-    if let Ok(meta) = path.metadata() {
-        meta.file_size()
-    } else {
-        0
-    }
+fn format_delta(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, format_size(delta.unsigned_abs()))
 }
 
-async fn calculate_dir_size(
-    path: &Path,
-    tx: &mut mpsc::Sender<Message>,
-    stop_rx: &mut mpsc::Receiver<Message>,
-) {
-    use std::fs;
+const MAX_RECENT_SNAPSHOTS: usize = 5;
 
-    #[derive(Clone)]
-    enum State {
-        Visiting,
-        Visited,
-    }
+const BATCH_CONFIRMATION: &str = "delete";
 
-    #[derive(Clone)]
-    struct Item {
-        path: PathBuf,
-        state: State,
-    }
+/// What has to be typed to delete a single folder for good.
+fn folder_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
 
-    let mut stack = vec![Item {
-        path: path.to_path_buf(),
-        state: State::Visiting,
-    }];
-    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+fn recent_snapshots_file() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("FindBigFolders").join("recent_snapshots.json"))
+}
 
-    while let Some(mut item) = stack.pop() {
-        match item.state {
-            State::Visiting => {
-                if item.path.is_file() {
-                    let alloc_size = get_allocated_size(&item.path);
-                    sizes.insert(item.path.clone(), alloc_size);
-                } else {
-                    item.state = State::Visited;
-                    stack.push(item.clone());
-                    if let Ok(entries) = fs::read_dir(&item.path) {
-                        for entry in entries.flatten() {
-                            let p = entry.path();
-                            if p.is_symlink() {
-                                continue;
-                            }
-                            stack.push(Item {
-                                path: p,
-                                state: State::Visiting,
-                            });
-                        }
-                    }
-                }
-            }
-            State::Visited => {
-                let mut size = get_allocated_size(&item.path);
-                if let Ok(entries) = fs::read_dir(&item.path) {
-                    for entry in entries.flatten() {
-                        let p = entry.path();
-                        if p.is_symlink() {
-                            continue;
-                        }
+fn load_recent_snapshots() -> Vec<PathBuf> {
+    recent_snapshots_file()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+        .and_then(|json| serde_json::from_str::<Vec<PathBuf>>(&json).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| path.is_file())
+        .take(MAX_RECENT_SNAPSHOTS)
+        .collect()
+}
 
-                        if p.is_file() {
-                            size += get_allocated_size(&p);
-                        } else if p.is_dir() {
-                            if let Some(s) = sizes.get(&p) {
-                                size += *s;
-                            }
-                            sizes.remove(&p);
-                        }
-                    }
-                }
-                sizes.insert(item.path.clone(), size);
-                let _ = tx
-                    .send(Message::Scanned(FileEntry {
-                        file: item.path.to_str().unwrap_or_default().to_string(),
-                        size: size,
-                        accessed: None,
-                    }))
-                    .await;
-                if let Ok(Some(Message::Stop)) = stop_rx.try_next() {
-                    return;
-                }
-            }
+// The list is only a convenience, so failing to save it is not reported.
+fn save_recent_snapshots(paths: &[PathBuf]) {
+    if let Some(file) = recent_snapshots_file() {
+        if let Some(dir) = file.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(json) = serde_json::to_string_pretty(paths) {
+            let _ = std::fs::write(file, json);
         }
     }
 }
 
-fn format_size(size: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size_f = size as f64;
-    let mut unit_index = 0;
-    while size_f >= 1024.0 && unit_index < UNITS.len() - 1 {
-        size_f /= 1024.0;
-        unit_index += 1;
-    }
-    format!("{:.1} {}", size_f, UNITS[unit_index])
+fn write_ncdu_file(
+    path: &Path,
+    root: &Path,
+    scanned_at: DateTime<Local>,
+    entries: &[FileEntry],
+) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    export::write_ncdu(&mut writer, root, scanned_at, entries)?;
+    writer.flush()
+}
+
+/// Runs file IO or parsing on tokio's blocking threads, so big files don't
+/// stall the executor.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()))
+}
+
+fn run_batch(action: Action, folders: Vec<PlannedFolder>) -> Task<Message> {
+    Task::perform(
+        async move {
+            let paths: Vec<PathBuf> = folders.iter().map(|folder| folder.path.clone()).collect();
+            // Removing, copying or packing gigabytes must not stall the executor.
+            let worker_action = action.clone();
+            let results =
+                tokio::task::spawn_blocking(move || cleanup::apply(&worker_action, &paths))
+                    .await
+                    .unwrap_or_else(|e| {
+                        folders
+                            .iter()
+                            .map(|folder| (folder.path.clone(), Err(e.to_string())))
+                            .collect()
+                    });
+            let results = folders
+                .into_iter()
+                .zip(results)
+                .map(|(folder, (_, result))| (folder, result))
+                .collect();
+            (action, results)
+        },
+        |(action, results)| Message::BatchDone(action, results),
+    )
 }
 
-async fn export_csv(entries: Vec<FileEntry>) {
-    let now = Local::now();
-    let timestamp = now.format("%Y-%m-%d_%H-%M-%S");
-    let filename = format!("findbigfolders_{}.csv", timestamp);
-    let dialog = AsyncFileDialog::new()
-        .add_filter("CSV", &["csv"])
+async fn save_path(name: &str, extension: &str, timestamp: DateTime<Local>) -> Option<PathBuf> {
+    let filename = format!(
+        "findbigfolders_{}.{}",
+        timestamp.format("%Y-%m-%d_%H-%M-%S"),
+        extension
+    );
+    AsyncFileDialog::new()
+        .add_filter(name, &[extension])
         .set_show_hidden_files(true)
         .set_file_name(&filename)
         .save_file()
-        .await;
-    if let Some(handle) = dialog {
-        let path = handle.path();
-        let mut wtr = Writer::from_path(path).unwrap();
-        wtr.write_record(&["File", "Size"]).unwrap();
-        for entry in entries {
-            if entry.size > 0 {
-                wtr.write_record(&[&entry.file, &format_size(entry.size)])
-                    .unwrap();
-            }
-        }
-        wtr.flush().unwrap();
-    }
+        .await
+        .map(|handle| handle.path().to_path_buf())
+}
+
+fn write_csv_file(
+    path: &Path,
+    root: &Path,
+    entries: &[FileEntry],
+    options: Option<&ScanOptions>,
+    csv_options: &CsvOptions,
+    read_metadata: bool,
+) -> csv::Result<()> {
+    let file = std::fs::File::create(path)?;
+    export::write_csv(
+        std::io::BufWriter::new(file),
+        root,
+        entries,
+        options,
+        csv_options,
+        read_metadata,
+    )
+}
+
+fn write_json_file(
+    path: &Path,
+    root: &Path,
+    scanned_at: DateTime<Local>,
+    entries: &[FileEntry],
+    options: Option<&ScanOptions>,
+    nested: bool,
+    read_metadata: bool,
+) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    export::write_json(
+        &mut writer,
+        root,
+        scanned_at,
+        entries,
+        options,
+        nested,
+        read_metadata,
+    )?;
+    writer.flush()
 }
 
 async fn scan_dirs(
     start_dir: &Path,
+    options: &ScanOptions,
     tx: &mut mpsc::Sender<Message>,
     stop_rx: &mut mpsc::Receiver<Message>,
 ) {
-    use std::fs;
-
-    if let Ok(dir_entries) = fs::read_dir(&start_dir) {
-        for entry in dir_entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                calculate_dir_size(&path, tx, stop_rx).await;
+    let mut scan = scanner::start(start_dir, options.clone());
+    loop {
+        // Wait for Stop alongside the next event, since a big folder can take
+        // a long time to report anything.
+        let event = match future::select(scan.next(), stop_rx.next()).await {
+            Either::Left((Some(event), _)) => event,
+            Either::Left((None, _)) => break,
+            Either::Right(_) => {
+                scan.cancel();
+                break;
             }
-        }
+        };
+        let message = match event {
+            ScanEvent::Scanned(entry) => Message::Scanned(entry),
+            ScanEvent::SkippedMount(path) => {
+                Message::SkippedMount(path.to_str().unwrap_or_default().to_string())
+            }
+        };
+        let _ = tx.send(message).await;
     }
 }
 
@@ -846,26 +3059,226 @@ fn scanner_subscription() -> impl futures::Stream<Item = Message> {
 
         loop {
             let msg = cmd_rx.try_next();
-            if let Ok(Some(Message::CurrentUser)) = msg {
-                if let Some(dir) = dirs::home_dir() {
-                    scan_dirs(&dir, &mut output, &mut stop_rx).await;
-                    let _ = output.send(Message::Done).await;
-                }
-            } else if let Ok(Some(Message::AllUsers)) = msg {
-                if let Some(dir) = dirs::home_dir() {
-                    if let Some(parent_dir) = dir.parent() {
-                        scan_dirs(&parent_dir, &mut output, &mut stop_rx).await;
-                        let _ = output.send(Message::Done).await;
-                    }
-                }
-            } else if let Ok(Some(Message::FolderSelected(path))) = msg {
-                if let Some(path) = path {
-                    scan_dirs(&path, &mut output, &mut stop_rx).await;
-                    let _ = output.send(Message::Done).await;
-                }
+            if let Ok(Some(Message::StartScan(root, options))) = msg {
+                while let Ok(Some(_)) = stop_rx.try_next() {}
+                scan_dirs(&root, &options, &mut output, &mut stop_rx).await;
+                let _ = output.send(Message::Done).await;
             } else if let Err(_) = msg {
                 tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
             }
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn entries() -> Vec<FileEntry> {
+        vec![FileEntry {
+            file: "/data/big".to_string(),
+            size: 4096,
+            apparent_size: 4000,
+            files: 3,
+            dirs: 0,
+            accessed: None,
+            excluded: false,
+        }]
+    }
+
+    /// Runs every exporter against `target`, expecting each to fail.
+    fn assert_exports_fail(target: &Path) {
+        let root = Path::new("/data");
+        let entries = entries();
+        let options = ScanOptions::default();
+        let scanned_at = Local::now();
+
+        assert!(write_csv_file(
+            target,
+            root,
+            &entries,
+            Some(&options),
+            &CsvOptions::default(),
+            true
+        )
+        .is_err());
+        assert!(write_json_file(
+            target,
+            root,
+            scanned_at,
+            &entries,
+            Some(&options),
+            false,
+            true
+        )
+        .is_err());
+        assert!(write_ncdu_file(target, root, scanned_at, &entries).is_err());
+        let snapshot = Snapshot::new(root.into(), scanned_at, Some(options), Vec::new(), entries);
+        assert!(snapshot.save(target).is_err());
+    }
+
+    #[test]
+    fn exports_to_a_missing_folder_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_exports_fail(&dir.path().join("missing").join("export"));
+    }
+
+    #[test]
+    fn exports_over_a_folder_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_exports_fail(dir.path());
+    }
+
+    #[test]
+    fn exports_to_a_read_only_folder_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut permissions = fs::metadata(dir.path()).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(dir.path(), permissions).unwrap();
+        // Root and admin accounts can write there anyway.
+        if fs::File::create(dir.path().join("probe")).is_ok() {
+            return;
+        }
+        assert_exports_fail(&dir.path().join("export"));
+    }
+
+    #[test]
+    fn excluded_folders_share_the_visible_rows() {
+        let folder = |file: String, size, excluded| FileEntry {
+            file,
+            size,
+            apparent_size: size,
+            files: 0,
+            dirs: 0,
+            accessed: None,
+            excluded,
+        };
+        let mut state = AppState::default();
+        state.settings.entries_visible = 10;
+        state.settings.show_last_accessed = false;
+        state.entries = (0..30)
+            .map(|i| folder(format!("/data/big{}", i), 100 + i, false))
+            .chain((0..1000).map(|i| folder(format!("/data/node_modules{}", i), 0, true)))
+            .collect();
+        state.bake_entries();
+
+        let visible = state.visible_entries();
+        assert_eq!(visible.len(), 10);
+        assert!(visible[..5].iter().all(|entry| !entry.excluded));
+        assert_eq!(visible[0].size, 129);
+        assert!(visible[5..].iter().all(|entry| entry.excluded));
+
+        // With few excluded folders the rest of the rows go to the biggest.
+        state
+            .entries
+            .retain(|entry| !entry.excluded || entry.file.ends_with("s7"));
+        state.bake_entries();
+        let visible = state.visible_entries();
+        assert_eq!(visible.len(), 10);
+        assert_eq!(visible.iter().filter(|entry| entry.excluded).count(), 1);
+    }
+
+    #[test]
+    fn loaded_scans_keep_their_own_access_times() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = AppState::default();
+        state.settings.show_last_accessed = true;
+        state.entries = vec![FileEntry {
+            file: dir.path().to_string_lossy().to_string(),
+            ..entries().remove(0)
+        }];
+        state.loaded_from = Some(dir.path().join("import.json"));
+        state.bake_entries();
+        assert_eq!(state.entries[0].accessed, None);
+
+        state.loaded_from = None;
+        state.bake_entries();
+        assert!(state.entries[0].accessed.is_some());
+    }
+
+    #[test]
+    fn settings_load_back_as_saved() {
+        let settings = AppSettings {
+            entries_visible: 50,
+            scan_threads: 3,
+            hard_links: HardLinkMode::CountEveryLink,
+            exclusions: vec!["node_modules".to_string()],
+            ..AppSettings::default()
+        };
+        let json = serde_json::to_string(&SettingsFile {
+            version: SETTINGS_VERSION,
+            settings,
+        })
+        .unwrap();
+        let loaded = AppSettings::from_json(&json);
+        assert_eq!((loaded.entries_visible, loaded.scan_threads), (50, 3));
+        assert_eq!(loaded.hard_links, HardLinkMode::CountEveryLink);
+        assert_eq!(loaded.exclusions, ["node_modules"]);
+    }
+
+    #[test]
+    fn unreadable_or_newer_settings_give_the_defaults() {
+        let newer = format!(
+            r#"{{"version": {}, "entries_visible": 50}}"#,
+            SETTINGS_VERSION + 1
+        );
+        for json in ["{", "[]", r#"{"entries_visible": 50}"#, newer.as_str()] {
+            assert_eq!(AppSettings::from_json(json).entries_visible, 20, "{}", json);
+        }
+    }
+
+    #[test]
+    fn one_bad_setting_only_resets_itself() {
+        let loaded = AppSettings::from_json(
+            r#"{"version": 1, "entries_visible": "lots", "scan_threads": 3,
+                "size_mode": "sideways", "show_excluded": true, "unknown": 1}"#,
+        );
+        assert_eq!(loaded.entries_visible, 20);
+        assert_eq!(loaded.scan_threads, 3);
+        assert_eq!(loaded.size_mode, SizeMode::OnDisk);
+        assert!(loaded.show_excluded);
+    }
+
+    #[test]
+    fn unusable_values_are_replaced() {
+        let loaded = AppSettings::from_json(
+            r#"{"version": 1, "scan_threads": 0, "exclusions": ["*.tmp", "re:(", "cache"]}"#,
+        );
+        assert_eq!(loaded.scan_threads, AppSettings::default().scan_threads);
+        assert_eq!(loaded.exclusions, ["*.tmp", "cache"]);
+    }
+
+    #[test]
+    fn plans_stay_open_while_busy() {
+        let mut state = AppState {
+            scanning: true,
+            pending_plan: Some(Plan::new(
+                PathBuf::from("/data"),
+                Action::Trash,
+                vec![PlannedFolder {
+                    path: PathBuf::from("/data/big"),
+                    size: 4096,
+                }],
+            )),
+            ..AppState::default()
+        };
+        let _ = state.handle(Message::RunPlan);
+        assert!(state.pending_plan.is_some());
+        assert!(state.show_wait_dialog);
+        assert!(!state.applying);
+    }
+
+    #[test]
+    fn batches_with_protected_folders_say_why_they_stop() {
+        let mut state = AppState {
+            scan_root: Some(PathBuf::from("/data")),
+            pending_batch: Some(Action::Trash),
+            ..AppState::default()
+        };
+        state.basket.insert("/data".to_string(), 4096);
+        let _ = state.handle(Message::RunBatch);
+        assert_eq!(state.status, "/data is protected and can't be removed");
+        assert!(!state.applying);
+    }
+}
@@ -0,0 +1,339 @@
+//! Guards and bookkeeping for removing folders found by a scan.
+
+use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Nothing inside these may be removed.
+#[cfg(unix)]
+const SYSTEM_TREES: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/lib", "/lib32", "/lib64", "/proc", "/sbin", "/sys", "/usr",
+    "/System",
+];
+#[cfg(windows)]
+const SYSTEM_TREES: &[&str] = &[r"C:\Windows"];
+
+// These may be cleaned out, but not removed themselves.
+#[cfg(unix)]
+const SYSTEM_DIRS: &[&str] = &[
+    "/home",
+    "/opt",
+    "/root",
+    "/run",
+    "/srv",
+    "/tmp",
+    "/var",
+    "/Applications",
+    "/Library",
+    "/Users",
+    "/Volumes",
+    "/private",
+];
+#[cfg(windows)]
+const SYSTEM_DIRS: &[&str] = &[
+    r"C:\Program Files",
+    r"C:\Program Files (x86)",
+    r"C:\ProgramData",
+    r"C:\Users",
+];
+
+/// Whether removing `path` would take out a system folder, the home folder,
+/// a filesystem root or the scanned folder itself.
+pub fn is_protected(path: &Path, scan_root: &Path) -> bool {
+    let mut candidates = vec![path.to_path_buf()];
+    if let Ok(canonical) = path.canonicalize() {
+        candidates.push(canonical);
+    }
+
+    let mut keep: Vec<PathBuf> = SYSTEM_DIRS
+        .iter()
+        .chain(SYSTEM_TREES)
+        .map(PathBuf::from)
+        .collect();
+    keep.push(scan_root.to_path_buf());
+    keep.extend(scan_root.canonicalize());
+    keep.extend(dirs::home_dir());
+
+    candidates.iter().any(|path| {
+        path.parent().is_none()
+            || SYSTEM_TREES.iter().any(|tree| path.starts_with(tree))
+            || keep.iter().any(|kept| kept.starts_with(path))
+    })
+}
+
+/// Where permanent deletions are recorded.
+pub fn deletion_log() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("FindBigFolders").join("deletions.log"))
+}
+
+/// Appends a tab-separated line with the time, the bytes reclaimed and the
+/// deleted path to the deletion log.
+pub fn log_deletion(path: &Path, bytes: u64) -> io::Result<()> {
+    let Some(log) = deletion_log() else {
+        return Ok(());
+    };
+    if let Some(dir) = log.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(log)?;
+    writeln!(
+        file,
+        "{}\t{}\t{}",
+        Local::now().to_rfc3339(),
+        bytes,
+        path.display()
+    )
+}
+
+/// What to do with a batch of folders.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Trash,
+    Delete,
+    /// Move into this folder, usually on another drive.
+    Move(PathBuf),
+    /// Pack into this `.tar.gz` archive, then remove the originals.
+    Compress(PathBuf),
+}
+
+/// The folder among `paths` that a move destination or archive lies inside,
+/// which would be lost along with the originals.
+pub fn conflicting_folder<'a>(action: &Action, paths: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let (Action::Move(target) | Action::Compress(target)) = action else {
+        return None;
+    };
+    paths.iter().find(|path| target.starts_with(path))
+}
+
+/// Applies `action` to each folder and reports how every one of them went.
+/// Nothing is removed for `Compress` unless the archive was written, and
+/// nothing at all is done when the destination is inside one of the folders.
+pub fn apply(action: &Action, paths: &[PathBuf]) -> Vec<(PathBuf, Result<(), String>)> {
+    if let Some(folder) = conflicting_folder(action, paths) {
+        let error = format!("the destination is inside {}", folder.display());
+        return paths
+            .iter()
+            .map(|path| (path.clone(), Err(error.clone())))
+            .collect();
+    }
+    let remove_all = || {
+        paths
+            .iter()
+            .map(|path| {
+                let result = std::fs::remove_dir_all(path).map_err(|e| e.to_string());
+                (path.clone(), result)
+            })
+            .collect()
+    };
+    match action {
+        Action::Trash => paths
+            .iter()
+            .map(|path| {
+                let result = trash::delete(path).map_err(|e| e.to_string());
+                (path.clone(), result)
+            })
+            .collect(),
+        Action::Delete => remove_all(),
+        Action::Move(destination) => paths
+            .iter()
+            .map(|path| {
+                let result = move_folder(path, destination)
+                    .map(|_| ())
+                    .map_err(|e| e.to_string());
+                (path.clone(), result)
+            })
+            .collect(),
+        Action::Compress(archive) => match compress_folders(paths, archive) {
+            Ok(()) => remove_all(),
+            Err(e) => {
+                let _ = std::fs::remove_file(archive);
+                paths
+                    .iter()
+                    .map(|path| (path.clone(), Err(e.to_string())))
+                    .collect()
+            }
+        },
+    }
+}
+
+/// Drops every path that lies inside another one in the list, so nested
+/// selections are only acted on, and counted, once.
+pub fn outermost(paths: &[PathBuf]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .filter(|path| {
+            !paths
+                .iter()
+                .any(|other| other != *path && path.starts_with(other))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Moves the folder at `path` into `destination`, copying it and removing
+/// the original when `destination` is on another filesystem. A copy that
+/// fails halfway is removed again.
+pub fn move_folder(path: &Path, destination: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "folder has no name"))?;
+    let target = destination.join(name);
+    if target.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", target.display()),
+        ));
+    }
+    match std::fs::rename(path, &target) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            if let Err(e) = copy_dir(path, &target) {
+                let _ = std::fs::remove_dir_all(&target);
+                return Err(e);
+            }
+            std::fs::remove_dir_all(path)?;
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(target)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::copy(from, to).map(|_| ())
+}
+
+/// Packs the folders into one gzip-compressed tar archive, each under its
+/// own name at the top of the archive.
+pub fn compress_folders(paths: &[PathBuf], archive: &Path) -> io::Result<()> {
+    let encoder = GzEncoder::new(
+        BufWriter::new(std::fs::File::create(archive)?),
+        Compression::default(),
+    );
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    let mut used = HashSet::new();
+    for path in paths {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "folder".to_string());
+        // Folders from different places can share a name.
+        let mut unique = name.clone();
+        let mut n = 1;
+        while !used.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{}-{}", name, n);
+        }
+        builder.append_dir_all(&unique, path)?;
+    }
+    builder.into_inner()?.finish()?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordinary_folders_below_the_scan_root_can_go() {
+        let root = tempfile::tempdir().unwrap();
+        let child = root.path().join("cache");
+        std::fs::create_dir(&child).unwrap();
+        assert!(!is_protected(&child, root.path()));
+        assert!(!is_protected(
+            &root.path().join("not/scanned/yet"),
+            root.path()
+        ));
+    }
+
+    #[test]
+    fn the_scan_root_and_its_parents_are_protected() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(is_protected(root.path(), root.path()));
+        assert!(is_protected(root.path().parent().unwrap(), root.path()));
+    }
+
+    #[test]
+    fn home_is_protected() {
+        let root = tempfile::tempdir().unwrap();
+        if let Some(home) = dirs::home_dir() {
+            assert!(is_protected(&home, root.path()));
+        }
+    }
+
+    #[test]
+    fn nothing_is_touched_when_the_destination_is_inside_a_folder() {
+        let root = tempfile::tempdir().unwrap();
+        let folder = root.path().join("cache");
+        std::fs::create_dir_all(folder.join("inner")).unwrap();
+        let paths = [folder.clone()];
+
+        for action in [
+            Action::Compress(folder.join("cache.tar.gz")),
+            Action::Move(folder.join("inner")),
+        ] {
+            assert_eq!(conflicting_folder(&action, &paths), Some(&folder));
+            let results = apply(&action, &paths);
+            assert!(results[0].1.is_err());
+            assert!(folder.join("inner").is_dir());
+        }
+        assert!(!folder.join("cache.tar.gz").exists());
+    }
+
+    #[test]
+    fn move_refuses_to_overwrite() {
+        let root = tempfile::tempdir().unwrap();
+        let folder = root.path().join("a").join("cache");
+        let destination = root.path().join("b");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::create_dir_all(destination.join("cache")).unwrap();
+
+        let error = move_folder(&folder, &destination).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert!(folder.is_dir());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn system_folders_are_protected() {
+        let root = Path::new("/");
+        assert!(is_protected(Path::new("/"), root));
+        assert!(is_protected(Path::new("/"), Path::new("/srv/data")));
+        assert!(is_protected(Path::new("/usr/x"), Path::new("/usr")));
+        assert!(is_protected(Path::new("/usr/x"), Path::new("/")));
+        assert!(is_protected(Path::new("/home"), Path::new("/")));
+        assert!(!is_protected(
+            Path::new("/home/someone/videos"),
+            Path::new("/")
+        ));
+    }
+}
@@ -0,0 +1,346 @@
+use chrono::Local;
+use du_gui_rs::export::{self, CsvOptions};
+use du_gui_rs::format_size;
+use du_gui_rs::scanner::{self, ExclusionRules, HardLinkMode, ScanOptions, SizeMode};
+use std::io::Write;
+use std::path::PathBuf;
+
+const USAGE: &str = "Usage: du-gui-rs [OPTIONS] <PATH>
+
+Scan PATH and print its biggest folders without opening a window.
+
+Options:
+  -n, --top <N>            Number of folders to print (default: 20)
+  -f, --format <FORMAT>    Output format: table, csv, json, ncdu or html
+                           (default: table)
+      --nested             With --format json, print every folder nested
+                           under its parent instead of the top N
+      --raw-sizes          With --format csv, print sizes in bytes
+  -x, --exclude <PATTERN>  Skip matching files and folders (repeatable)
+  -j, --threads <N>        Number of scanner threads
+      --one-file-system    Don't cross into other mounted filesystems
+      --apparent-size      Sort and report by apparent size
+      --count-links        Count every hard link instead of once per file
+  -h, --help               Print this help";
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Table,
+    Csv,
+    Json,
+    Ncdu,
+    Html,
+}
+
+struct Args {
+    path: PathBuf,
+    top: usize,
+    format: Format,
+    nested: bool,
+    raw_sizes: bool,
+    exclusions: Vec<String>,
+    threads: Option<usize>,
+    one_file_system: bool,
+    size_mode: SizeMode,
+    hard_links: HardLinkMode,
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Args>, String> {
+    let mut path = None;
+    let mut top = 20;
+    let mut format = Format::Table;
+    let mut nested = false;
+    let mut raw_sizes = false;
+    let mut exclusions = Vec::new();
+    let mut threads = None;
+    let mut one_file_system = false;
+    let mut size_mode = SizeMode::OnDisk;
+    let mut hard_links = HardLinkMode::CountOnce;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-n" | "--top" => {
+                top = value(arg)?
+                    .parse()
+                    .map_err(|_| format!("invalid value for {}", arg))?;
+            }
+            "-f" | "--format" => {
+                format = match value(arg)?.as_str() {
+                    "table" => Format::Table,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    "ncdu" => Format::Ncdu,
+                    "html" => Format::Html,
+                    other => return Err(format!("unknown format '{}'", other)),
+                };
+            }
+            "--nested" => nested = true,
+            "--raw-sizes" => raw_sizes = true,
+            "-x" | "--exclude" => exclusions.push(value(arg)?),
+            "-j" | "--threads" => {
+                let num: usize = value(arg)?
+                    .parse()
+                    .map_err(|_| format!("invalid value for {}", arg))?;
+                if num == 0 {
+                    return Err(format!("{} must be at least 1", arg));
+                }
+                threads = Some(num);
+            }
+            "--one-file-system" => one_file_system = true,
+            "--apparent-size" => size_mode = SizeMode::Apparent,
+            "--count-links" => hard_links = HardLinkMode::CountEveryLink,
+            other if other.starts_with('-') => return Err(format!("unknown option '{}'", other)),
+            other => {
+                if path.is_some() {
+                    return Err(format!("unexpected argument '{}'", other));
+                }
+                path = Some(PathBuf::from(other));
+            }
+        }
+    }
+
+    let path = path.ok_or_else(|| "missing PATH".to_string())?;
+    Ok(Some(Args {
+        path,
+        top,
+        format,
+        nested,
+        raw_sizes,
+        exclusions,
+        threads,
+        one_file_system,
+        size_mode,
+        hard_links,
+    }))
+}
+
+/// The arguments to run headless with, or `None` to open the window. Any
+/// argument at all means the command line, except the process serial number
+/// macOS passes when launched from Finder.
+pub fn headless_args(args: impl IntoIterator<Item = String>) -> Option<Vec<String>> {
+    let args: Vec<String> = args
+        .into_iter()
+        .filter(|arg| !arg.starts_with("-psn_"))
+        .collect();
+    (!args.is_empty()).then_some(args)
+}
+
+/// Runs a scan from the command line and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if !args.path.is_dir() {
+        eprintln!("error: {} is not a folder", args.path.display());
+        return 2;
+    }
+
+    let exclusions = match ExclusionRules::new(&args.exclusions) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("error: invalid exclusion pattern: {}", e);
+            return 2;
+        }
+    };
+    let defaults = ScanOptions::default();
+    let options = ScanOptions {
+        threads: args.threads.unwrap_or(defaults.threads),
+        hard_links: args.hard_links,
+        one_file_system: args.one_file_system,
+        exclusions,
+        ..defaults
+    };
+
+    let scanned_at = Local::now();
+    let results = scanner::start(&args.path, options.clone()).wait();
+
+    let mut entries = results.entries;
+    entries.sort_by(|a, b| b.size_for(args.size_mode).cmp(&a.size_for(args.size_mode)));
+    // Nested JSON and ncdu output describe the whole hierarchy, and the HTML
+    // report needs every folder for its totals and treemap.
+    if !args.nested && !matches!(args.format, Format::Ncdu | Format::Html) {
+        entries.truncate(args.top);
+    }
+
+    for path in results.skipped_mounts {
+        eprintln!("skipped mount point {}", path.display());
+    }
+
+    let stdout = std::io::stdout();
+    let result = match args.format {
+        Format::Table => {
+            let mut out = stdout.lock();
+            writeln!(
+                out,
+                "{:>10}  {:>10}  {:>10}  Folder",
+                "Size", "Files", "Subfolders"
+            )
+            .and_then(|_| {
+                entries.iter().try_for_each(|entry| {
+                    writeln!(
+                        out,
+                        "{:>10}  {:>10}  {:>10}  {}",
+                        format_size(entry.size_for(args.size_mode)),
+                        entry.files,
+                        entry.dirs,
+                        entry.file
+                    )
+                })
+            })
+        }
+        Format::Csv => export::write_csv(
+            stdout.lock(),
+            &args.path,
+            &entries,
+            Some(&options),
+            &CsvOptions {
+                raw_sizes: args.raw_sizes,
+                ..CsvOptions::default()
+            },
+            true,
+        )
+        .map_err(std::io::Error::from),
+        Format::Json => {
+            let mut out = stdout.lock();
+            export::write_json(
+                &mut out,
+                &args.path,
+                scanned_at,
+                &entries,
+                Some(&options),
+                args.nested,
+                true,
+            )
+            .map_err(std::io::Error::from)
+            .and_then(|_| writeln!(out))
+        }
+        Format::Ncdu => {
+            let mut out = stdout.lock();
+            export::write_ncdu(&mut out, &args.path, scanned_at, &entries)
+                .map_err(std::io::Error::from)
+                .and_then(|_| writeln!(out))
+        }
+        Format::Html => export::write_html(
+            stdout.lock(),
+            &args.path,
+            scanned_at,
+            &entries,
+            Some(&options),
+            args.size_mode,
+            args.top,
+        ),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn error(args: &[&str]) -> String {
+        parse(args).err().unwrap()
+    }
+
+    #[test]
+    fn defaults_need_only_a_path() {
+        let args = parse(&["/data"]).unwrap().unwrap();
+        assert_eq!(args.path, PathBuf::from("/data"));
+        assert_eq!(args.top, 20);
+        assert_eq!(args.format, Format::Table);
+        assert_eq!(args.threads, None);
+        assert_eq!(args.size_mode, SizeMode::OnDisk);
+        assert_eq!(args.hard_links, HardLinkMode::CountOnce);
+        assert_eq!(error(&[]), "missing PATH");
+        assert!(parse(&["--help"]).unwrap().is_none());
+    }
+
+    #[test]
+    fn unknown_options_and_extra_paths_are_rejected() {
+        assert_eq!(error(&["--bogus", "/data"]), "unknown option '--bogus'");
+        assert_eq!(error(&["/a", "/b"]), "unexpected argument '/b'");
+    }
+
+    #[test]
+    fn options_need_their_values() {
+        assert_eq!(error(&["/data", "--top"]), "--top needs a value");
+        assert_eq!(error(&["/data", "-x"]), "-x needs a value");
+        assert_eq!(error(&["/data", "-n", "many"]), "invalid value for -n");
+        assert_eq!(error(&["/data", "-j", "0"]), "-j must be at least 1");
+    }
+
+    #[test]
+    fn formats_are_recognised() {
+        for (name, expected) in [
+            ("table", Format::Table),
+            ("csv", Format::Csv),
+            ("json", Format::Json),
+            ("ncdu", Format::Ncdu),
+            ("html", Format::Html),
+        ] {
+            let args = parse(&["--format", name, "/data"]).unwrap().unwrap();
+            assert_eq!(args.format, expected);
+        }
+        assert_eq!(error(&["-f", "xml", "/data"]), "unknown format 'xml'");
+    }
+
+    #[test]
+    fn exclusions_and_flags_are_collected() {
+        let args = parse(&[
+            "-x",
+            "node_modules",
+            "/data",
+            "--exclude",
+            "re:^/data/tmp",
+            "--count-links",
+            "--apparent-size",
+            "--one-file-system",
+            "-j",
+            "3",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.exclusions, ["node_modules", "re:^/data/tmp"]);
+        assert_eq!(args.hard_links, HardLinkMode::CountEveryLink);
+        assert_eq!(args.size_mode, SizeMode::Apparent);
+        assert!(args.one_file_system);
+        assert_eq!(args.threads, Some(3));
+    }
+
+    #[test]
+    fn any_argument_runs_headless() {
+        let args = |args: &[&str]| headless_args(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(args(&[]), None);
+        assert_eq!(args(&["-psn_0_12345"]), None);
+        assert_eq!(args(&["/data"]), Some(vec!["/data".to_string()]));
+        assert_eq!(args(&["--bogus"]), Some(vec!["--bogus".to_string()]));
+        assert_eq!(args(&["-psn_0_1", "-h"]), Some(vec!["-h".to_string()]));
+    }
+}
@@ -0,0 +1,190 @@
+//! Folder size changes between a baseline scan and a newer scan of the same
+//! root.
+
+use crate::scanner::{FileEntry, SizeMode};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Grown,
+    Shrunk,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Change::Added => "Added",
+            Change::Removed => "Removed",
+            Change::Grown => "Grown",
+            Change::Shrunk => "Shrunk",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffSort {
+    #[default]
+    Absolute,
+    Percent,
+}
+
+impl DiffSort {
+    pub const ALL: [DiffSort; 2] = [DiffSort::Absolute, DiffSort::Percent];
+}
+
+impl std::fmt::Display for DiffSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DiffSort::Absolute => "Biggest change",
+            DiffSort::Percent => "Biggest change in %",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FolderDiff {
+    pub path: String,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+    pub change: Change,
+}
+
+impl FolderDiff {
+    pub fn delta(&self) -> i64 {
+        self.after.unwrap_or(0) as i64 - self.before.unwrap_or(0) as i64
+    }
+
+    /// Change relative to the baseline size, or `None` when the folder had
+    /// nothing to compare against.
+    pub fn percent(&self) -> Option<f64> {
+        match self.before {
+            Some(before) if before > 0 => Some(self.delta() as f64 * 100.0 / before as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Lists every folder whose size differs between `baseline` and `current`.
+/// Folders of the same size in both scans are left out.
+pub fn compare(
+    baseline: &[FileEntry],
+    current: &[FileEntry],
+    size_mode: SizeMode,
+) -> Vec<FolderDiff> {
+    let before: HashMap<&str, u64> = baseline
+        .iter()
+        .map(|entry| (entry.file.as_str(), entry.size_for(size_mode)))
+        .collect();
+    let after: HashMap<&str, u64> = current
+        .iter()
+        .map(|entry| (entry.file.as_str(), entry.size_for(size_mode)))
+        .collect();
+
+    let mut diffs: Vec<FolderDiff> = current
+        .iter()
+        .filter_map(|entry| {
+            let size = entry.size_for(size_mode);
+            let change = match before.get(entry.file.as_str()) {
+                None => Change::Added,
+                Some(&old) if size > old => Change::Grown,
+                Some(&old) if size < old => Change::Shrunk,
+                Some(_) => return None,
+            };
+            Some(FolderDiff {
+                path: entry.file.clone(),
+                before: before.get(entry.file.as_str()).copied(),
+                after: Some(size),
+                change,
+            })
+        })
+        .collect();
+    diffs.extend(
+        baseline
+            .iter()
+            .filter(|entry| !after.contains_key(entry.file.as_str()))
+            .map(|entry| FolderDiff {
+                path: entry.file.clone(),
+                before: Some(entry.size_for(size_mode)),
+                after: None,
+                change: Change::Removed,
+            }),
+    );
+    diffs
+}
+
+/// Sorts the biggest changes first, growth and shrinkage alike. Added
+/// folders have no percentage and come first when sorting by percent.
+pub fn sort(diffs: &mut [FolderDiff], by: DiffSort) {
+    match by {
+        DiffSort::Absolute => {
+            diffs.sort_by_key(|diff| std::cmp::Reverse(diff.delta().unsigned_abs()))
+        }
+        DiffSort::Percent => diffs.sort_by(|a, b| {
+            let key = |diff: &FolderDiff| diff.percent().map_or(f64::INFINITY, f64::abs);
+            key(b).total_cmp(&key(a))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, size: u64) -> FileEntry {
+        FileEntry {
+            file: file.to_string(),
+            size,
+            apparent_size: size * 2,
+            files: 0,
+            dirs: 0,
+            accessed: None,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn compare_classifies_every_change() {
+        let baseline = [
+            entry("/r/same", 10),
+            entry("/r/grown", 10),
+            entry("/r/shrunk", 10),
+            entry("/r/removed", 10),
+        ];
+        let current = [
+            entry("/r/same", 10),
+            entry("/r/grown", 25),
+            entry("/r/shrunk", 4),
+            entry("/r/added", 7),
+        ];
+        let mut diffs = compare(&baseline, &current, SizeMode::OnDisk);
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let summary: Vec<(&str, Change, i64)> = diffs
+            .iter()
+            .map(|d| (d.path.as_str(), d.change, d.delta()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("/r/added", Change::Added, 7),
+                ("/r/grown", Change::Grown, 15),
+                ("/r/removed", Change::Removed, -10),
+                ("/r/shrunk", Change::Shrunk, -6),
+            ]
+        );
+        assert_eq!(diffs[0].percent(), None);
+        assert_eq!(diffs[1].percent(), Some(150.0));
+    }
+
+    #[test]
+    fn compare_uses_the_size_mode() {
+        let diffs = compare(
+            &[entry("/r/a", 10)],
+            &[entry("/r/a", 12)],
+            SizeMode::Apparent,
+        );
+        assert_eq!((diffs[0].before, diffs[0].after), (Some(20), Some(24)));
+    }
+}
@@ -0,0 +1,887 @@
+//! CSV, JSON, ncdu and HTML exports of a scan.
+
+use crate::scanner::{FileEntry, HardLinkMode, ScanOptions, SizeMode};
+use crate::tree::{self, LayoutRect, ScanTree, LABEL_HEIGHT};
+use crate::{aggregate_by_name, format_size};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvDelimiter {
+    #[default]
+    Comma,
+    Semicolon,
+    Tab,
+}
+
+impl CsvDelimiter {
+    pub const ALL: [CsvDelimiter; 3] = [
+        CsvDelimiter::Comma,
+        CsvDelimiter::Semicolon,
+        CsvDelimiter::Tab,
+    ];
+
+    fn as_byte(self) -> u8 {
+        match self {
+            CsvDelimiter::Comma => b',',
+            CsvDelimiter::Semicolon => b';',
+            CsvDelimiter::Tab => b'\t',
+        }
+    }
+}
+
+impl std::fmt::Display for CsvDelimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CsvDelimiter::Comma => "Comma",
+            CsvDelimiter::Semicolon => "Semicolon",
+            CsvDelimiter::Tab => "Tab",
+        })
+    }
+}
+
+/// What goes into a CSV export. The defaults match the original export:
+/// human-readable sizes with file and subfolder counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvOptions {
+    pub raw_sizes: bool,
+    pub counts: bool,
+    pub accessed: bool,
+    pub modified: bool,
+    pub depth: bool,
+    pub owner: bool,
+    pub delimiter: CsvDelimiter,
+    pub keep_empty: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            raw_sizes: false,
+            counts: true,
+            accessed: false,
+            modified: false,
+            depth: false,
+            owner: false,
+            delimiter: CsvDelimiter::default(),
+            keep_empty: false,
+        }
+    }
+}
+
+/// Writes the scanned folders as CSV. Every row records how hard links were
+/// counted, which is left blank for imported scans. Depth is counted from
+/// `root`.
+///
+/// Times and owners the entries don't carry are looked up on disk only with
+/// `read_metadata` set. Leave it unset for imported or reopened scans, whose
+/// folders may be on another machine or have changed since.
+pub fn write_csv<W: Write>(
+    writer: W,
+    root: &Path,
+    entries: &[FileEntry],
+    options: Option<&ScanOptions>,
+    csv_options: &CsvOptions,
+    read_metadata: bool,
+) -> csv::Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(csv_options.delimiter.as_byte())
+        .from_writer(writer);
+
+    let mut header = vec!["File", "Size on Disk", "Apparent Size", "Hard Links"];
+    if csv_options.counts {
+        header.extend(["Files", "Subfolders"]);
+    }
+    if csv_options.accessed {
+        header.push("Last Accessed");
+    }
+    if csv_options.modified {
+        header.push("Last Modified");
+    }
+    if csv_options.depth {
+        header.push("Depth");
+    }
+    if csv_options.owner {
+        header.push("Owner");
+    }
+    wtr.write_record(&header)?;
+
+    let size = |bytes: u64| {
+        if csv_options.raw_sizes {
+            bytes.to_string()
+        } else {
+            format_size(bytes)
+        }
+    };
+    let time = |time: Option<DateTime<Local>>| {
+        time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    };
+    let hard_links = options
+        .map(|options| options.hard_links.to_string())
+        .unwrap_or_default();
+    let needs_metadata =
+        read_metadata && (csv_options.accessed || csv_options.modified || csv_options.owner);
+
+    for entry in entries {
+        if entry.size == 0 && entry.apparent_size == 0 && !csv_options.keep_empty {
+            continue;
+        }
+        let metadata = if needs_metadata {
+            std::fs::metadata(&entry.file).ok()
+        } else {
+            None
+        };
+
+        let mut record = vec![
+            entry.file.clone(),
+            size(entry.size),
+            size(entry.apparent_size),
+            hard_links.clone(),
+        ];
+        if csv_options.counts {
+            record.push(entry.files.to_string());
+            record.push(entry.dirs.to_string());
+        }
+        if csv_options.accessed {
+            record.push(time(entry.accessed.or_else(|| {
+                metadata
+                    .as_ref()
+                    .and_then(|m| m.accessed().ok())
+                    .map(DateTime::<Local>::from)
+            })));
+        }
+        if csv_options.modified {
+            record.push(time(
+                metadata
+                    .as_ref()
+                    .and_then(|m| m.modified().ok())
+                    .map(DateTime::<Local>::from),
+            ));
+        }
+        if csv_options.depth {
+            let depth = Path::new(&entry.file)
+                .strip_prefix(root)
+                .map(|relative| relative.components().count())
+                .unwrap_or_default();
+            record.push(depth.to_string());
+        }
+        if csv_options.owner {
+            record.push(metadata.as_ref().map(owner).unwrap_or_default());
+        }
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn owner(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+
+    let uid = metadata.uid();
+    uzers::get_user_by_uid(uid)
+        .map(|user| user.name().to_string_lossy().to_string())
+        .unwrap_or_else(|| uid.to_string())
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &std::fs::Metadata) -> String {
+    String::new()
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    root: String,
+    scanned_at: String,
+    settings: Option<JsonSettings<'a>>,
+    folders: Vec<JsonFolder>,
+}
+
+#[derive(Serialize)]
+struct JsonSettings<'a> {
+    threads: usize,
+    hard_links: HardLinkMode,
+    one_file_system: bool,
+    exclusions: &'a [String],
+    show_excluded: bool,
+}
+
+#[derive(Serialize)]
+struct JsonFolder {
+    path: String,
+    size: u64,
+    apparent_size: u64,
+    files: u64,
+    folders: u64,
+    excluded: bool,
+    modified: Option<String>,
+    accessed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<JsonFolder>>,
+}
+
+impl JsonFolder {
+    fn new(entry: &FileEntry, read_metadata: bool) -> Self {
+        let metadata = read_metadata
+            .then(|| std::fs::metadata(&entry.file).ok())
+            .flatten();
+        let modified = metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .map(DateTime::<Local>::from);
+        let accessed = entry.accessed.or_else(|| {
+            metadata
+                .as_ref()
+                .and_then(|m| m.accessed().ok())
+                .map(DateTime::<Local>::from)
+        });
+        Self {
+            path: entry.file.clone(),
+            size: entry.size,
+            apparent_size: entry.apparent_size,
+            files: entry.files,
+            folders: entry.dirs,
+            excluded: entry.excluded,
+            modified: modified.map(|t| t.to_rfc3339()),
+            accessed: accessed.map(|t| t.to_rfc3339()),
+            children: None,
+        }
+    }
+}
+
+/// Writes the scan as JSON with raw byte sizes. With `nested` set, every
+/// folder carries a `children` array instead of being listed flat. Without
+/// `options`, as for imported scans, `settings` is null. `read_metadata` is
+/// as for [`write_csv`].
+pub fn write_json<W: Write>(
+    writer: W,
+    root: &Path,
+    scanned_at: DateTime<Local>,
+    entries: &[FileEntry],
+    options: Option<&ScanOptions>,
+    nested: bool,
+    read_metadata: bool,
+) -> serde_json::Result<()> {
+    let folders = if nested {
+        let tree = ScanTree::build(root, entries, SizeMode::OnDisk);
+        let by_path: HashMap<&str, &FileEntry> =
+            entries.iter().map(|e| (e.file.as_str(), e)).collect();
+        nest(&tree, tree.root(), &by_path, read_metadata)
+    } else {
+        entries
+            .iter()
+            .map(|entry| JsonFolder::new(entry, read_metadata))
+            .collect()
+    };
+    let report = JsonReport {
+        root: root.to_string_lossy().to_string(),
+        scanned_at: scanned_at.to_rfc3339(),
+        settings: options.map(|options| JsonSettings {
+            threads: options.threads,
+            hard_links: options.hard_links,
+            one_file_system: options.one_file_system,
+            exclusions: options.exclusions.patterns(),
+            show_excluded: options.show_excluded,
+        }),
+        folders,
+    };
+    serde_json::to_writer_pretty(writer, &report)
+}
+
+fn nest(
+    tree: &ScanTree,
+    id: usize,
+    by_path: &HashMap<&str, &FileEntry>,
+    read_metadata: bool,
+) -> Vec<JsonFolder> {
+    tree.node(id)
+        .children
+        .iter()
+        .filter_map(|&child| {
+            let entry = by_path.get(tree.node(child).path.to_string_lossy().as_ref())?;
+            let mut folder = JsonFolder::new(entry, read_metadata);
+            folder.children = Some(nest(tree, child, by_path, read_metadata));
+            Some(folder)
+        })
+        .collect()
+}
+
+/// Writes the scan in the format of `ncdu -o`, so it can be browsed with
+/// `ncdu -f`. Individual files aren't kept by the scanner, so whatever a
+/// folder holds besides its subfolders is reported as the folder's own size.
+/// Sizes and subfolder counts survive reading the dump back, but file counts
+/// come back as zero.
+pub fn write_ncdu<W: Write>(
+    writer: W,
+    root: &Path,
+    scanned_at: DateTime<Local>,
+    entries: &[FileEntry],
+) -> serde_json::Result<()> {
+    let tree = ScanTree::build(root, entries, SizeMode::OnDisk);
+    let by_path: HashMap<&str, &FileEntry> = entries.iter().map(|e| (e.file.as_str(), e)).collect();
+
+    let mut root_dir = vec![json!({ "name": root.to_string_lossy() })];
+    root_dir.extend(ncdu_dirs(&tree, tree.root(), &by_path));
+    let dump = json!([
+        1,
+        2,
+        {
+            "progname": "FindBigFolders",
+            "progver": env!("CARGO_PKG_VERSION"),
+            "timestamp": scanned_at.timestamp(),
+        },
+        root_dir,
+    ]);
+    serde_json::to_writer(writer, &dump)
+}
+
+fn ncdu_dirs(tree: &ScanTree, id: usize, by_path: &HashMap<&str, &FileEntry>) -> Vec<Value> {
+    tree.node(id)
+        .children
+        .iter()
+        .filter_map(|&child| {
+            let node = tree.node(child);
+            let entry = by_path.get(node.path.to_string_lossy().as_ref())?;
+            let (mut size, mut apparent_size) = (entry.size, entry.apparent_size);
+            for &grandchild in &node.children {
+                if let Some(sub) =
+                    by_path.get(tree.node(grandchild).path.to_string_lossy().as_ref())
+                {
+                    size = size.saturating_sub(sub.size);
+                    apparent_size = apparent_size.saturating_sub(sub.apparent_size);
+                }
+            }
+
+            let mut info = json!({
+                "name": node.name,
+                "asize": apparent_size,
+                "dsize": size,
+            });
+            if entry.excluded {
+                info["excluded"] = json!("pattern");
+                return Some(info);
+            }
+            let mut dir = vec![info];
+            dir.extend(ncdu_dirs(tree, child, by_path));
+            Some(Value::Array(dir))
+        })
+        .collect()
+}
+
+const TREEMAP_WIDTH: f32 = 960.0;
+const TREEMAP_HEIGHT: f32 = 540.0;
+
+/// Writes a single self-contained HTML page with the scan settings, totals,
+/// a treemap of the biggest folders and the `top` biggest folders and folder
+/// names, for people who don't run the app.
+pub fn write_html<W: Write>(
+    mut writer: W,
+    root: &Path,
+    scanned_at: DateTime<Local>,
+    entries: &[FileEntry],
+    options: Option<&ScanOptions>,
+    size_mode: SizeMode,
+    top: usize,
+) -> std::io::Result<()> {
+    let tree = ScanTree::build(root, entries, size_mode);
+    let root_node = tree.node(tree.root());
+    let total = root_node.size;
+    let (files, folders) = entries
+        .iter()
+        .filter(|entry| Path::new(&entry.file).parent() == Some(root))
+        .fold((0, 0), |(files, dirs), entry| {
+            (files + entry.files, dirs + entry.dirs + 1)
+        });
+    let title = format!("FindBigFolders report for {}", root.display());
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(
+        writer,
+        "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">"
+    )?;
+    writeln!(writer, "<title>{}</title>", escape(&title))?;
+    writeln!(
+        writer,
+        "<style>
+body {{ font-family: sans-serif; margin: 2em; color: #222; }}
+table {{ border-collapse: collapse; margin-bottom: 2em; }}
+th, td {{ padding: 4px 12px; border-bottom: 1px solid #ddd; text-align: left; }}
+td.num, th.num {{ text-align: right; }}
+svg text {{ font-size: 12px; pointer-events: none; }}
+</style>\n</head>\n<body>"
+    )?;
+    writeln!(writer, "<h1>{}</h1>", escape(&title))?;
+
+    writeln!(writer, "<h2>Scan</h2>\n<table>")?;
+    // Imported scans don't record how they were made.
+    let setting = |value: &dyn Fn(&ScanOptions) -> String| {
+        options
+            .map(value)
+            .unwrap_or_else(|| "Not recorded".to_string())
+    };
+    for (name, value) in [
+        ("Folder", root.display().to_string()),
+        ("Scanned", scanned_at.format("%Y-%m-%d %H:%M").to_string()),
+        ("Sizes", size_mode.to_string()),
+        (
+            "Hard-linked files",
+            setting(&|options| options.hard_links.to_string()),
+        ),
+        (
+            "Stay on one filesystem",
+            setting(&|options| if options.one_file_system { "Yes" } else { "No" }.to_string()),
+        ),
+        (
+            "Excluded",
+            setting(&|options| {
+                let exclusions = options.exclusions.patterns().join(", ");
+                if exclusions.is_empty() {
+                    "Nothing".to_string()
+                } else {
+                    exclusions
+                }
+            }),
+        ),
+        ("Total size", format_size(total)),
+        ("Files", files.to_string()),
+        ("Folders", folders.to_string()),
+    ] {
+        writeln!(
+            writer,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            escape(&value)
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Treemap</h2>")?;
+    writeln!(
+        writer,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {w} {h}\" width=\"{w}\" height=\"{h}\">",
+        w = TREEMAP_WIDTH,
+        h = TREEMAP_HEIGHT
+    )?;
+    let bounds = LayoutRect {
+        x: 0.0,
+        y: 0.0,
+        width: TREEMAP_WIDTH,
+        height: TREEMAP_HEIGHT,
+    };
+    svg_cells(&mut writer, &tree, bounds)?;
+    writeln!(writer, "</svg>")?;
+
+    let mut biggest: Vec<&FileEntry> = entries.iter().collect();
+    biggest.sort_by(|a, b| b.size_for(size_mode).cmp(&a.size_for(size_mode)));
+    writeln!(writer, "<h2>Biggest folders</h2>\n<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Folder</th><th class=\"num\">Size</th><th class=\"num\">Share</th>\
+         <th class=\"num\">Files</th><th class=\"num\">Subfolders</th></tr>"
+    )?;
+    for entry in biggest.iter().take(top) {
+        let size = entry.size_for(size_mode);
+        let share = if total > 0 {
+            size as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td class=\"num\" title=\"{} bytes\">{}</td><td class=\"num\">{:.1}%</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            escape(&entry.file),
+            size,
+            format_size(size),
+            share,
+            entry.files,
+            entry.dirs
+        )?;
+    }
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Biggest folder names</h2>\n<table>")?;
+    writeln!(
+        writer,
+        "<tr><th>Folder Name</th><th class=\"num\">Total Size</th><th class=\"num\">Count</th></tr>"
+    )?;
+    for aggregated in aggregate_by_name(entries, size_mode).iter().take(top) {
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td class=\"num\" title=\"{} bytes\">{}</td><td class=\"num\">{}</td></tr>",
+            escape(&aggregated.name),
+            aggregated.total_size,
+            format_size(aggregated.total_size),
+            aggregated.count
+        )?;
+    }
+    writeln!(writer, "</table>\n</body>\n</html>")?;
+    writer.flush()
+}
+
+fn svg_cells<W: Write>(writer: &mut W, tree: &ScanTree, bounds: LayoutRect) -> std::io::Result<()> {
+    for cell in tree::treemap(tree, tree.root(), bounds) {
+        let node = tree.node(cell.node);
+        let rect = cell.rect;
+        let [r, g, b] = tree::PALETTE[cell.color].map(|c| (c * 255.0).round() as u8);
+        writeln!(
+            writer,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#{:02x}{:02x}{:02x}\" \
+             fill-opacity=\"{}\" stroke=\"#555\"><title>{} ({})</title></rect>",
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            r,
+            g,
+            b,
+            if cell.depth == 1 { "1" } else { "0.7" },
+            escape(&node.path.to_string_lossy()),
+            format_size(node.size)
+        )?;
+        if rect.width > 30.0 && rect.height > LABEL_HEIGHT {
+            let max_chars = ((rect.width - 6.0) / 7.0) as usize;
+            let label: String = node.name.chars().take(max_chars).collect();
+            writeln!(
+                writer,
+                "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+                rect.x + 3.0,
+                rect.y + 13.0,
+                escape(&label)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(file: &str, size: u64) -> FileEntry {
+        FileEntry {
+            file: file.to_string(),
+            size,
+            apparent_size: size / 2,
+            files: 3,
+            dirs: 1,
+            accessed: None,
+            excluded: false,
+        }
+    }
+
+    fn csv(
+        entries: &[FileEntry],
+        options: Option<&ScanOptions>,
+        csv_options: CsvOptions,
+    ) -> String {
+        let mut out = Vec::new();
+        write_csv(
+            &mut out,
+            Path::new("/r"),
+            entries,
+            options,
+            &csv_options,
+            false,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_records_the_hard_link_mode_on_every_row() {
+        let options = ScanOptions {
+            hard_links: HardLinkMode::CountEveryLink,
+            ..ScanOptions::default()
+        };
+        let entries = [entry("/r/a", 2048), entry("/r/b", 1024)];
+        let lines: Vec<String> = csv(&entries, Some(&options), CsvOptions::default())
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert!(lines[0].starts_with("File,Size on Disk,Apparent Size,Hard Links,"));
+        assert_eq!(lines.len(), 3);
+        for line in &lines[1..] {
+            assert_eq!(line.split(',').nth(3), Some("Count every link"));
+        }
+
+        // Imported scans don't say, so the column is left blank.
+        let imported = csv(&entries, None, CsvOptions::default());
+        assert!(imported
+            .lines()
+            .skip(1)
+            .all(|line| line.split(',').nth(3) == Some("")));
+    }
+
+    #[test]
+    fn folder_metadata_is_only_read_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let entries = [entry(dir.path().to_str().unwrap(), 1024)];
+        let csv_options = CsvOptions {
+            modified: true,
+            owner: true,
+            ..CsvOptions::default()
+        };
+        let last_fields = |read_metadata| {
+            let mut out = Vec::new();
+            write_csv(
+                &mut out,
+                dir.path(),
+                &entries,
+                None,
+                &csv_options,
+                read_metadata,
+            )
+            .unwrap();
+            let out = String::from_utf8(out).unwrap();
+            let row = out.lines().nth(1).unwrap().to_string();
+            row.rsplitn(3, ',').take(2).collect::<Vec<_>>().join(",")
+        };
+        assert_eq!(last_fields(false), ",");
+        assert_ne!(last_fields(true), ",");
+
+        let mut out = Vec::new();
+        write_json(
+            &mut out,
+            dir.path(),
+            Local::now(),
+            &entries,
+            None,
+            false,
+            false,
+        )
+        .unwrap();
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        assert!(json["folders"][0]["modified"].is_null());
+    }
+
+    #[test]
+    fn ncdu_export_reads_back_with_the_same_sizes() {
+        let folder = |file: &str, size, apparent_size, files, dirs| FileEntry {
+            file: file.to_string(),
+            size,
+            apparent_size,
+            files,
+            dirs,
+            accessed: None,
+            excluded: false,
+        };
+        let entries = [
+            folder("/r/a", 12288, 5106, 2, 1),
+            folder("/r/a/b", 8192, 5000, 1, 0),
+            folder("/r/c", 4096, 4096, 0, 0),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.json");
+        let file = std::fs::File::create(&path).unwrap();
+        write_ncdu(file, Path::new("/r"), Local::now(), &entries).unwrap();
+
+        let dump = crate::ncdu::read(&path, HardLinkMode::CountOnce).unwrap();
+        assert_eq!(dump.root, Path::new("/r"));
+        assert_eq!(dump.entries.len(), entries.len());
+        for entry in &entries {
+            let read = dump.entries.iter().find(|e| e.file == entry.file).unwrap();
+            assert_eq!(
+                (read.size, read.apparent_size, read.dirs, read.files),
+                (entry.size, entry.apparent_size, entry.dirs, 0),
+                "{}",
+                entry.file
+            );
+        }
+    }
+
+    fn json(options: Option<&ScanOptions>, nested: bool) -> Value {
+        let entries = [
+            entry("/r/a", 3000),
+            entry("/r/a/b", 1000),
+            entry("/r/c", 2000),
+        ];
+        let mut out = Vec::new();
+        write_json(
+            &mut out,
+            Path::new("/r"),
+            Local::now(),
+            &entries,
+            options,
+            nested,
+            false,
+        )
+        .unwrap();
+        serde_json::from_slice(&out).unwrap()
+    }
+
+    fn paths(folders: &Value) -> Vec<&str> {
+        folders
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|folder| folder["path"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn json_lists_folders_flat_by_default() {
+        let report = json(None, false);
+        assert_eq!(report["root"], "/r");
+        assert_eq!(paths(&report["folders"]), ["/r/a", "/r/a/b", "/r/c"]);
+        let a = &report["folders"][0];
+        assert_eq!(
+            (a["size"].as_u64(), a["apparent_size"].as_u64()),
+            (Some(3000), Some(1500))
+        );
+        assert!(a.get("children").is_none());
+    }
+
+    #[test]
+    fn nested_json_puts_folders_under_their_parents() {
+        let report = json(None, true);
+        let folders = &report["folders"];
+        assert_eq!(paths(folders), ["/r/a", "/r/c"]);
+        assert_eq!(paths(&folders[0]["children"]), ["/r/a/b"]);
+        assert_eq!(folders[0]["children"][0]["children"], json!([]));
+    }
+
+    #[test]
+    fn json_settings_are_null_unless_recorded() {
+        assert!(json(None, false)["settings"].is_null());
+
+        let options = ScanOptions {
+            threads: 3,
+            one_file_system: true,
+            ..ScanOptions::default()
+        };
+        let settings = &json(Some(&options), false)["settings"];
+        assert_eq!(settings["threads"], 3);
+        assert_eq!(settings["one_file_system"], true);
+        assert_eq!(settings["exclusions"], json!([]));
+    }
+
+    #[test]
+    fn csv_sizes_are_human_readable_unless_raw() {
+        let entries = [entry("/r/a", 2048)];
+        let human = csv(&entries, None, CsvOptions::default());
+        assert_eq!(human.lines().nth(1), Some("/r/a,2.0 KB,1.0 KB,,3,1"));
+        let raw = csv(
+            &entries,
+            None,
+            CsvOptions {
+                raw_sizes: true,
+                ..CsvOptions::default()
+            },
+        );
+        assert_eq!(raw.lines().nth(1), Some("/r/a,2048,1024,,3,1"));
+    }
+
+    #[test]
+    fn csv_delimiter_and_empty_folders_follow_the_options() {
+        let entries = [entry("/r/a", 2048), entry("/r/empty", 0)];
+        let out = csv(
+            &entries,
+            None,
+            CsvOptions {
+                delimiter: CsvDelimiter::Semicolon,
+                ..CsvOptions::default()
+            },
+        );
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.lines().all(|line| line.split(';').count() == 6));
+
+        let out = csv(
+            &entries,
+            None,
+            CsvOptions {
+                delimiter: CsvDelimiter::Tab,
+                keep_empty: true,
+                ..CsvOptions::default()
+            },
+        );
+        assert_eq!(out.lines().nth(2), Some("/r/empty\t0.0 B\t0.0 B\t\t3\t1"));
+    }
+
+    #[test]
+    fn csv_columns_follow_the_options() {
+        let entries = [entry("/r/a/b", 2048)];
+        let header = |csv_options| {
+            csv(&entries, None, csv_options)
+                .lines()
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            header(CsvOptions {
+                counts: false,
+                ..CsvOptions::default()
+            }),
+            "File,Size on Disk,Apparent Size,Hard Links"
+        );
+        let all = CsvOptions {
+            accessed: true,
+            modified: true,
+            depth: true,
+            owner: true,
+            ..CsvOptions::default()
+        };
+        assert_eq!(
+            header(all),
+            "File,Size on Disk,Apparent Size,Hard Links,Files,Subfolders,\
+             Last Accessed,Last Modified,Depth,Owner"
+        );
+        let row = csv(&entries, None, all);
+        assert_eq!(row.lines().nth(1), Some("/r/a/b,2.0 KB,1.0 KB,,3,1,,,2,"));
+    }
+
+    fn html(options: Option<&ScanOptions>) -> String {
+        let entries = [
+            entry("/r/<b>&c", 3072),
+            entry("/r/<b>&c/d", 1024),
+            entry("/r/e", 1024),
+        ];
+        let mut out = Vec::new();
+        write_html(
+            &mut out,
+            Path::new("/r"),
+            Local::now(),
+            &entries,
+            options,
+            SizeMode::OnDisk,
+            10,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn html_escapes_folder_names() {
+        let page = html(None);
+        assert!(page.contains("<td>/r/&lt;b&gt;&amp;c</td>"));
+        assert!(page.contains("<td>&lt;b&gt;&amp;c</td>"));
+        assert!(!page.contains("<b>&c"));
+    }
+
+    #[test]
+    fn html_totals_cover_the_top_level_folders() {
+        let page = html(Some(&ScanOptions::default()));
+        assert!(page.contains("<tr><th>Total size</th><td>4.0 KB</td></tr>"));
+        // Each folder holds 3 files and 1 subfolder, plus itself.
+        assert!(page.contains("<tr><th>Files</th><td>6</td></tr>"));
+        assert!(page.contains("<tr><th>Folders</th><td>4</td></tr>"));
+        assert!(page.contains("<tr><th>Hard-linked files</th><td>Count once</td></tr>"));
+
+        assert!(html(None).contains("<tr><th>Hard-linked files</th><td>Not recorded</td></tr>"));
+    }
+}
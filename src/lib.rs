@@ -0,0 +1,57 @@
+//! Scanning, size accounting and export logic behind FindBigFolders,
+//! independent of the GUI.
+
+pub mod cleanup;
+pub mod diff;
+pub mod export;
+pub mod ncdu;
+pub mod plan;
+pub mod scanner;
+pub mod snapshot;
+pub mod tree;
+
+use scanner::{FileEntry, SizeMode};
+use std::collections::HashMap;
+use std::path::Path;
+
+pub fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size_f = size as f64;
+    let mut unit_index = 0;
+    while size_f >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size_f /= 1024.0;
+        unit_index += 1;
+    }
+    format!("{:.1} {}", size_f, UNITS[unit_index])
+}
+
+pub struct AggregatedEntry {
+    pub name: String,
+    pub total_size: u64,
+    pub count: usize,
+}
+
+/// Adds up the folders that share a name, e.g. every `node_modules`,
+/// biggest total first.
+pub fn aggregate_by_name(entries: &[FileEntry], size_mode: SizeMode) -> Vec<AggregatedEntry> {
+    let mut name_map: HashMap<String, (u64, usize)> = HashMap::new();
+
+    for entry in entries {
+        if let Some(name) = Path::new(&entry.file).file_name().and_then(|n| n.to_str()) {
+            let (total_size, count) = name_map.entry(name.to_string()).or_insert((0, 0));
+            *total_size += entry.size_for(size_mode);
+            *count += 1;
+        }
+    }
+
+    let mut aggregated: Vec<AggregatedEntry> = name_map
+        .into_iter()
+        .map(|(name, (total_size, count))| AggregatedEntry {
+            name,
+            total_size,
+            count,
+        })
+        .collect();
+    aggregated.sort_by(|a, b| b.total_size.cmp(&a.total_size));
+    aggregated
+}
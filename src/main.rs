@@ -1,12 +1,21 @@
 mod app;
+mod cli;
 mod styles;
+mod sunburst;
+mod treemap;
 use app::AppState;
 use iced::Theme;
 
 fn main() -> iced::Result {
+    if let Some(args) = cli::headless_args(std::env::args().skip(1)) {
+        std::process::exit(cli::run(&args));
+    }
+
     iced::application("FindBigFolders", AppState::update, AppState::view)
         .subscription(AppState::subscription)
         .theme(theme)
+        // Lets the app save anything still being typed before it exits.
+        .exit_on_close_request(false)
         .run_with(AppState::new)
 }
 
@@ -0,0 +1,269 @@
+//! Reading dumps written by `ncdu -o`.
+//!
+//! The format is `[major, minor, metadata, root]`, where a directory is an
+//! array holding its own info object followed by its files (objects) and
+//! subdirectories (arrays). Sizes and counts are accumulated the same way
+//! the scanner does, so imported folders look exactly like scanned ones.
+
+use crate::scanner::{DirTotals, FileEntry, HardLinkMode};
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct NcduDump {
+    pub root: PathBuf,
+    pub scanned_at: Option<DateTime<Local>>,
+    pub entries: Vec<FileEntry>,
+    pub skipped_mounts: Vec<PathBuf>,
+}
+
+pub fn read(path: &Path, hard_links: HardLinkMode) -> io::Result<NcduDump> {
+    let dump: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let parts = dump.as_array().ok_or_else(|| invalid("not an ncdu dump"))?;
+    if parts.first().and_then(Value::as_u64) != Some(1) {
+        return Err(invalid("unsupported ncdu dump version"));
+    }
+    let root_dir = parts
+        .get(3)
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("ncdu dump has no root folder"))?;
+    let root = root_dir
+        .first()
+        .and_then(|info| info.get("name"))
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| invalid("ncdu dump has no root folder"))?;
+    let scanned_at = parts
+        .get(2)
+        .and_then(|meta| meta.get("timestamp"))
+        .and_then(Value::as_i64)
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|t| t.with_timezone(&Local));
+
+    let mut reader = Reader {
+        hard_links,
+        seen_inodes: HashSet::new(),
+        entries: Vec::new(),
+        skipped_mounts: Vec::new(),
+    };
+    reader.read_dir(root_dir, &root, 0, true);
+    Ok(NcduDump {
+        root,
+        scanned_at,
+        entries: reader.entries,
+        skipped_mounts: reader.skipped_mounts,
+    })
+}
+
+struct Reader {
+    hard_links: HardLinkMode,
+    seen_inodes: HashSet<(u64, u64)>,
+    entries: Vec<FileEntry>,
+    skipped_mounts: Vec<PathBuf>,
+}
+
+impl Reader {
+    fn read_dir(
+        &mut self,
+        dir: &[Value],
+        path: &Path,
+        parent_dev: u64,
+        is_root: bool,
+    ) -> DirTotals {
+        let Some(info) = dir.first() else {
+            return DirTotals::default();
+        };
+        let dev = info
+            .get("dev")
+            .and_then(Value::as_u64)
+            .unwrap_or(parent_dev);
+        let mut totals = sizes(info);
+
+        for item in &dir[1..] {
+            match item {
+                Value::Array(subdir) => {
+                    let Some(name) = subdir.first().and_then(name) else {
+                        continue;
+                    };
+                    totals = totals + self.read_dir(subdir, &path.join(name), dev, false);
+                    totals.dirs += 1;
+                }
+                Value::Object(_) => {
+                    if let Some(reason) = item.get("excluded").and_then(Value::as_str) {
+                        // Older ncdu versions write "othfs".
+                        if reason == "otherfs" || reason == "othfs" {
+                            if let Some(name) = name(item) {
+                                self.skipped_mounts.push(path.join(name));
+                            }
+                        }
+                        continue;
+                    }
+                    if item.get("notreg").and_then(Value::as_bool) == Some(true) {
+                        continue;
+                    }
+                    totals = totals + self.file_sizes(item, dev);
+                    totals.files += 1;
+                }
+                _ => {}
+            }
+        }
+
+        if !is_root {
+            self.entries.push(FileEntry {
+                file: path.to_string_lossy().to_string(),
+                size: totals.size,
+                apparent_size: totals.apparent_size,
+                files: totals.files,
+                dirs: totals.dirs,
+                accessed: None,
+                excluded: false,
+            });
+        }
+        totals
+    }
+
+    fn file_sizes(&mut self, file: &Value, dev: u64) -> DirTotals {
+        let hard_linked = file.get("hlnkc").and_then(Value::as_bool) == Some(true);
+        if hard_linked && self.hard_links == HardLinkMode::CountOnce {
+            if let Some(ino) = file.get("ino").and_then(Value::as_u64) {
+                if !self.seen_inodes.insert((dev, ino)) {
+                    return DirTotals::default();
+                }
+            }
+        }
+        sizes(file)
+    }
+}
+
+fn name(info: &Value) -> Option<&str> {
+    info.get("name").and_then(Value::as_str)
+}
+
+// ncdu leaves out fields that are zero.
+fn sizes(info: &Value) -> DirTotals {
+    let field = |key: &str| info.get(key).and_then(Value::as_u64).unwrap_or(0);
+    DirTotals {
+        size: field("dsize"),
+        apparent_size: field("asize"),
+        files: 0,
+        dirs: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_json(json: &str, hard_links: HardLinkMode) -> io::Result<NcduDump> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dump.json");
+        std::fs::write(&path, json).unwrap();
+        read(&path, hard_links)
+    }
+
+    fn folder<'a>(dump: &'a NcduDump, path: &str) -> &'a FileEntry {
+        dump.entries
+            .iter()
+            .find(|entry| entry.file == path)
+            .unwrap()
+    }
+
+    #[test]
+    fn nested_folders_add_up_like_a_scan() {
+        let dump = read_json(
+            r#"[1, 2, {"progname": "ncdu", "timestamp": 1700000000},
+                [{"name": "/r", "dsize": 4096},
+                 {"name": "top", "asize": 10, "dsize": 4096},
+                 [{"name": "a", "asize": 6, "dsize": 4096},
+                  {"name": "f1", "asize": 100, "dsize": 4096},
+                  [{"name": "b", "dsize": 4096},
+                   {"name": "f2", "asize": 5000, "dsize": 8192}]]]]"#,
+            HardLinkMode::CountOnce,
+        )
+        .unwrap();
+        assert_eq!(dump.root, PathBuf::from("/r"));
+        assert_eq!(dump.scanned_at.unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(dump.entries.len(), 2);
+
+        let b = folder(&dump, "/r/a/b");
+        assert_eq!(
+            (b.size, b.apparent_size, b.files, b.dirs),
+            (12288, 5000, 1, 0)
+        );
+        let a = folder(&dump, "/r/a");
+        assert_eq!(
+            (a.size, a.apparent_size, a.files, a.dirs),
+            (20480, 5106, 2, 1)
+        );
+    }
+
+    #[test]
+    fn hard_links_count_once_per_inode_unless_asked() {
+        let json = r#"[1, 0, {},
+            [{"name": "/r", "dev": 7},
+             [{"name": "a"}, {"name": "f", "dsize": 1000, "hlnkc": true, "ino": 42}],
+             [{"name": "b"}, {"name": "g", "dsize": 1000, "hlnkc": true, "ino": 42},
+              {"name": "h", "dsize": 1000, "hlnkc": true, "ino": 43}]]]"#;
+        let total = |hard_links| {
+            let dump = read_json(json, hard_links).unwrap();
+            dump.entries.iter().map(|entry| entry.size).sum::<u64>()
+        };
+        assert_eq!(total(HardLinkMode::CountOnce), 2000);
+        assert_eq!(total(HardLinkMode::CountEveryLink), 3000);
+    }
+
+    #[test]
+    fn excluded_and_special_entries_are_skipped() {
+        let dump = read_json(
+            r#"[1, 2, {},
+                [{"name": "/r"},
+                 [{"name": "a"},
+                  {"name": "cache", "excluded": "pattern"},
+                  {"name": "mnt", "excluded": "otherfs"},
+                  {"name": "old", "excluded": "othfs"},
+                  {"name": "fifo", "notreg": true, "dsize": 4096},
+                  {"name": "empty"},
+                  {"name": "data", "dsize": 512}]]]"#,
+            HardLinkMode::CountOnce,
+        )
+        .unwrap();
+        let a = folder(&dump, "/r/a");
+        // Only "empty" and "data" are files, and "empty" has no sizes at all.
+        assert_eq!((a.size, a.apparent_size, a.files, a.dirs), (512, 0, 2, 0));
+        assert_eq!(
+            dump.skipped_mounts,
+            [PathBuf::from("/r/a/mnt"), PathBuf::from("/r/a/old")]
+        );
+    }
+
+    #[test]
+    fn malformed_dumps_are_errors() {
+        for json in [
+            "not json",
+            "{}",
+            "[]",
+            r#"[2, 0, {}, [{"name": "/r"}]]"#,
+            r#"["1", 0, {}, [{"name": "/r"}]]"#,
+            r#"[1, 0, {}]"#,
+            r#"[1, 0, {}, []]"#,
+            r#"[1, 0, {}, [{"size": 1}]]"#,
+        ] {
+            let error = read_json(json, HardLinkMode::CountOnce).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", json);
+        }
+
+        // Items of the wrong type are ignored rather than trusted.
+        let dump = read_json(
+            r#"[1, 0, {}, [{"name": "/r"}, 5, "x", [], [{"dsize": 1}], [{"name": "a"}]]]"#,
+            HardLinkMode::CountOnce,
+        )
+        .unwrap();
+        assert_eq!(dump.entries.len(), 1);
+    }
+}
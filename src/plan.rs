@@ -0,0 +1,254 @@
+//! Cleanup plans: folders picked for cleanup and what to do with them,
+//! written to a file so they can be reviewed before anything is touched.
+//!
+//! A plan is saved as JSON, which the app can load back and carry out, or
+//! as a shell script that does the same thing without the app.
+
+use crate::cleanup::Action;
+use crate::format_size;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedFolder {
+    pub path: PathBuf,
+    /// Size on disk when the plan was made.
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Plan {
+    version: u32,
+    pub created_at: DateTime<Local>,
+    /// The scanned folder the plan was made from.
+    pub root: PathBuf,
+    pub action: Action,
+    pub folders: Vec<PlannedFolder>,
+    pub reclaimable: u64,
+}
+
+impl Plan {
+    pub fn new(root: PathBuf, action: Action, folders: Vec<PlannedFolder>) -> Self {
+        Self {
+            version: VERSION,
+            created_at: Local::now(),
+            root,
+            action,
+            reclaimable: folders.iter().map(|folder| folder.size).sum(),
+            folders,
+        }
+    }
+
+    /// Writes a shell script when `path` ends in `.sh`, JSON otherwise.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if path.extension().is_some_and(|ext| ext == "sh") {
+            // Built first so a plan the script can't express leaves no file.
+            let mut script = Vec::new();
+            self.write_script(&mut script)?;
+            std::fs::write(path, script)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+            }
+            Ok(())
+        } else {
+            let mut writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut writer, self)?;
+            writer.flush()
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let plan: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if plan.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported plan version {}", plan.version),
+            ));
+        }
+        Ok(plan)
+    }
+
+    /// POSIX shell script carrying out the plan, stopping at the first
+    /// command that fails.
+    ///
+    /// Fails for a compress plan with two folders of the same name: the app
+    /// renames the second one inside the archive, which plain `tar` can't.
+    pub fn write_script<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Action::Compress(_) = &self.action {
+            let mut names = HashSet::new();
+            for folder in &self.folders {
+                let name = folder.path.file_name().unwrap_or_default();
+                if !names.insert(name) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "more than one folder is named {}, save the plan as JSON instead",
+                            Path::new(name).display()
+                        ),
+                    ));
+                }
+            }
+        }
+        let action = match &self.action {
+            Action::Trash => "move to the trash".to_string(),
+            Action::Delete => "delete permanently".to_string(),
+            Action::Move(destination) => format!("move into {}", destination.display()),
+            Action::Compress(archive) => format!(
+                "compress into {} and remove the originals",
+                archive.display()
+            ),
+        };
+        writeln!(writer, "#!/bin/sh")?;
+        writeln!(
+            writer,
+            "# Cleanup plan made by FindBigFolders on {}",
+            self.created_at.format("%Y-%m-%d %H:%M")
+        )?;
+        writeln!(writer, "# Scanned folder: {}", self.root.display())?;
+        writeln!(writer, "# Action: {}", action)?;
+        writeln!(
+            writer,
+            "# {} folders, {} reclaimable",
+            self.folders.len(),
+            format_size(self.reclaimable)
+        )?;
+        writeln!(writer, "# Review the folders below before running this.")?;
+        writeln!(writer, "set -eu")?;
+        writeln!(writer)?;
+
+        match &self.action {
+            Action::Trash => {
+                writeln!(
+                    writer,
+                    "if command -v gio >/dev/null 2>&1; then to_trash() {{ gio trash -- \"$1\"; }}"
+                )?;
+                writeln!(
+                    writer,
+                    "elif command -v trash-put >/dev/null 2>&1; then to_trash() {{ trash-put -- \"$1\"; }}"
+                )?;
+                writeln!(
+                    writer,
+                    "elif command -v trash >/dev/null 2>&1; then to_trash() {{ trash \"$1\"; }}"
+                )?;
+                writeln!(
+                    writer,
+                    "else echo \"No trash command found, install gio or trash-cli\" >&2; exit 1; fi"
+                )?;
+                for folder in &self.folders {
+                    writeln!(writer)?;
+                    writeln!(writer, "# {}", format_size(folder.size))?;
+                    writeln!(writer, "to_trash {}", quote(&folder.path))?;
+                }
+            }
+            Action::Delete => {
+                for folder in &self.folders {
+                    writeln!(writer)?;
+                    writeln!(writer, "# {}", format_size(folder.size))?;
+                    writeln!(writer, "rm -rf -- {}", quote(&folder.path))?;
+                }
+            }
+            Action::Move(destination) => {
+                for folder in &self.folders {
+                    // Like the app, never merge into or replace what is there.
+                    let target = destination.join(folder.path.file_name().unwrap_or_default());
+                    writeln!(writer)?;
+                    writeln!(writer, "# {}", format_size(folder.size))?;
+                    writeln!(
+                        writer,
+                        "[ -e {0} ] && {{ echo {1} >&2; exit 1; }}",
+                        quote(&target),
+                        quote(Path::new(&format!("{} already exists", target.display())))
+                    )?;
+                    writeln!(writer, "mv -- {} {}", quote(&folder.path), quote(&target))?;
+                }
+            }
+            Action::Compress(archive) => {
+                write!(writer, "tar -czf {}", quote(archive))?;
+                for folder in &self.folders {
+                    let parent = folder.path.parent().unwrap_or(Path::new("/"));
+                    let name = folder.path.file_name().unwrap_or_default();
+                    write!(
+                        writer,
+                        " \\\n    -C {} {}",
+                        quote(parent),
+                        quote(Path::new(name))
+                    )?;
+                }
+                writeln!(writer)?;
+                for folder in &self.folders {
+                    writeln!(writer)?;
+                    writeln!(writer, "# {}", format_size(folder.size))?;
+                    writeln!(writer, "rm -rf -- {}", quote(&folder.path))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Single-quotes `path` for the shell.
+fn quote(path: &Path) -> String {
+    format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(path: PathBuf) -> PlannedFolder {
+        std::fs::create_dir_all(&path).unwrap();
+        PlannedFolder { path, size: 0 }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn move_script_stops_instead_of_merging_folders() {
+        let root = tempfile::tempdir().unwrap();
+        let destination = root.path().join("dest");
+        std::fs::create_dir(&destination).unwrap();
+        let first = folder(root.path().join("a/cache"));
+        let second = folder(root.path().join("b/cache"));
+        let plan = Plan::new(
+            root.path().to_path_buf(),
+            Action::Move(destination.clone()),
+            vec![first.clone(), second.clone()],
+        );
+        let script = root.path().join("plan.sh");
+        plan.save(&script).unwrap();
+
+        let status = std::process::Command::new("sh")
+            .arg(&script)
+            .status()
+            .unwrap();
+        assert!(!status.success());
+        assert!(destination.join("cache").is_dir());
+        assert!(!first.path.exists());
+        assert!(second.path.is_dir());
+        assert!(!destination.join("cache/cache").exists());
+    }
+
+    #[test]
+    fn compress_script_refuses_folders_sharing_a_name() {
+        let root = tempfile::tempdir().unwrap();
+        let plan = Plan::new(
+            root.path().to_path_buf(),
+            Action::Compress(root.path().join("cleanup.tar.gz")),
+            vec![
+                folder(root.path().join("a/cache")),
+                folder(root.path().join("b/cache")),
+            ],
+        );
+        let script = root.path().join("plan.sh");
+        assert!(plan.save(&script).is_err());
+        assert!(!script.exists());
+        assert!(plan.save(&root.path().join("plan.json")).is_ok());
+    }
+}
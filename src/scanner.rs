@@ -0,0 +1,679 @@
+//! Parallel folder size scanner.
+//!
+//! [`start`] runs a scan on a background thread and returns a [`Scan`] that
+//! streams [`ScanEvent`]s as folders finish, can be cancelled at any time,
+//! and can be waited on for the collected [`ScanResults`].
+
+use chrono::{DateTime, Local};
+use futures::channel::mpsc;
+use futures::{Stream, StreamExt};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub file: String,
+    pub size: u64,
+    pub apparent_size: u64,
+    pub files: u64,
+    pub dirs: u64,
+    pub accessed: Option<DateTime<Local>>,
+    pub excluded: bool,
+}
+
+impl FileEntry {
+    pub fn size_for(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::OnDisk => self.size,
+            SizeMode::Apparent => self.apparent_size,
+        }
+    }
+}
+
+/// Drops the folder at `path` and everything below it from `entries`, and
+/// takes its sizes and counts off every folder above it, as if it had never
+/// been scanned. Returns the removed folder's entry.
+pub fn remove_folder(entries: &mut Vec<FileEntry>, path: &Path) -> Option<FileEntry> {
+    let removed = entries
+        .iter()
+        .find(|entry| Path::new(&entry.file) == path)?
+        .clone();
+    entries.retain(|entry| !Path::new(&entry.file).starts_with(path));
+    for entry in entries.iter_mut() {
+        if path.starts_with(&entry.file) {
+            entry.size = entry.size.saturating_sub(removed.size);
+            entry.apparent_size = entry.apparent_size.saturating_sub(removed.apparent_size);
+            entry.files = entry.files.saturating_sub(removed.files);
+            entry.dirs = entry.dirs.saturating_sub(removed.dirs + 1);
+        }
+    }
+    Some(removed)
+}
+
+/// On Windows both sizes are the file length.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeMode {
+    #[default]
+    OnDisk,
+    Apparent,
+}
+
+impl SizeMode {
+    pub const ALL: [SizeMode; 2] = [SizeMode::OnDisk, SizeMode::Apparent];
+
+    /// Whether the two modes can differ on this platform.
+    pub const DISTINCT: bool = cfg!(unix);
+}
+
+impl std::fmt::Display for SizeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SizeMode::OnDisk => "Size on disk",
+            SizeMode::Apparent => "Apparent size",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HardLinkMode {
+    #[default]
+    CountOnce,
+    CountEveryLink,
+}
+
+impl HardLinkMode {
+    pub const ALL: [HardLinkMode; 2] = [HardLinkMode::CountOnce, HardLinkMode::CountEveryLink];
+}
+
+impl std::fmt::Display for HardLinkMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            HardLinkMode::CountOnce => "Count once",
+            HardLinkMode::CountEveryLink => "Count every link",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScanOptions {
+    pub threads: usize,
+    pub hard_links: HardLinkMode,
+    pub one_file_system: bool,
+    pub exclusions: ExclusionRules,
+    pub show_excluded: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            hard_links: HardLinkMode::default(),
+            one_file_system: false,
+            exclusions: ExclusionRules::default(),
+            show_excluded: false,
+        }
+    }
+}
+
+/// Patterns starting with `re:` are regular expressions matched against the
+/// full path. Other patterns are globs, matched against the full path if they
+/// contain a path separator and against the file name otherwise.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>", into = "Vec<String>")]
+pub struct ExclusionRules {
+    patterns: Vec<String>,
+    names: GlobSet,
+    paths: GlobSet,
+    regexes: Vec<Regex>,
+}
+
+impl ExclusionRules {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let mut names = GlobSetBuilder::new();
+        let mut paths = GlobSetBuilder::new();
+        let mut regexes = Vec::new();
+        for pattern in patterns {
+            if let Some(re) = pattern.strip_prefix("re:") {
+                regexes.push(Regex::new(re).map_err(|e| e.to_string())?);
+            } else {
+                let glob = Glob::new(pattern).map_err(|e| e.to_string())?;
+                if pattern.contains('/') || pattern.contains('\\') {
+                    paths.add(glob);
+                } else {
+                    names.add(glob);
+                }
+            }
+        }
+        Ok(Self {
+            patterns: patterns.to_vec(),
+            names: names.build().map_err(|e| e.to_string())?,
+            paths: paths.build().map_err(|e| e.to_string())?,
+            regexes,
+        })
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.paths.is_empty() && self.regexes.is_empty()
+    }
+
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        if let Some(name) = path.file_name() {
+            if self.names.is_match(name) {
+                return true;
+            }
+        }
+        if self.paths.is_match(path) {
+            return true;
+        }
+        let path = path.to_string_lossy();
+        self.regexes.iter().any(|re| re.is_match(&path))
+    }
+}
+
+impl TryFrom<Vec<String>> for ExclusionRules {
+    type Error = String;
+
+    fn try_from(patterns: Vec<String>) -> Result<Self, String> {
+        Self::new(&patterns)
+    }
+}
+
+impl From<ExclusionRules> for Vec<String> {
+    fn from(rules: ExclusionRules) -> Self {
+        rules.patterns
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ScanEvent {
+    Scanned(FileEntry),
+    SkippedMount(PathBuf),
+}
+
+#[cfg(unix)]
+fn get_allocated_size(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.blocks() * 512
+}
+
+#[cfg(windows)]
+fn get_allocated_size(meta: &Metadata) -> u64 {
+    use std::os::windows::fs::MetadataExt;
+    meta.file_size()
+}
+
+#[cfg(unix)]
+fn hard_link_key(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if meta.nlink() > 1 {
+        Some((meta.dev(), meta.ino()))
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+fn hard_link_key(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn device_id(meta: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(windows)]
+fn device_id(_meta: &Metadata) -> Option<u64> {
+    None
+}
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct DirTotals {
+    pub(crate) size: u64,
+    pub(crate) apparent_size: u64,
+    pub(crate) files: u64,
+    pub(crate) dirs: u64,
+}
+
+impl DirTotals {
+    fn of(meta: &Metadata) -> Self {
+        Self {
+            size: get_allocated_size(meta),
+            apparent_size: meta.len(),
+            files: 0,
+            dirs: 0,
+        }
+    }
+}
+
+impl std::ops::Add for DirTotals {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            size: self.size + other.size,
+            apparent_size: self.apparent_size + other.apparent_size,
+            files: self.files + other.files,
+            dirs: self.dirs + other.dirs,
+        }
+    }
+}
+
+struct ScanContext<'a> {
+    options: &'a ScanOptions,
+    cancel: &'a AtomicBool,
+    sink: &'a (dyn Fn(ScanEvent) + Sync),
+    seen_inodes: Mutex<HashSet<(u64, u64)>>,
+    root_device: Option<u64>,
+}
+
+impl ScanContext<'_> {
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if !self.options.exclusions.is_excluded(path) {
+            return false;
+        }
+        if is_dir && self.options.show_excluded {
+            (self.sink)(ScanEvent::Scanned(FileEntry {
+                file: path.to_str().unwrap_or_default().to_string(),
+                size: 0,
+                apparent_size: 0,
+                files: 0,
+                dirs: 0,
+                accessed: None,
+                excluded: true,
+            }));
+        }
+        true
+    }
+
+    fn file_totals(&self, meta: &Metadata) -> DirTotals {
+        if self.options.hard_links == HardLinkMode::CountOnce {
+            if let Some(key) = hard_link_key(meta) {
+                if !self.seen_inodes.lock().unwrap().insert(key) {
+                    return DirTotals::default();
+                }
+            }
+        }
+        DirTotals::of(meta)
+    }
+
+    fn crosses_mount(&self, path: &Path, meta: &Metadata) -> bool {
+        match self.root_device {
+            Some(root_device) if device_id(meta) != Some(root_device) => {
+                (self.sink)(ScanEvent::SkippedMount(path.to_path_buf()));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn calculate_dir_size(path: &Path, own: DirTotals, ctx: &ScanContext) -> DirTotals {
+    use std::fs;
+
+    if ctx.cancel.load(Ordering::Relaxed) {
+        return DirTotals::default();
+    }
+
+    let mut totals = own;
+    let mut subdirs = Vec::new();
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            // `DirEntry::file_type` and `DirEntry::metadata` don't follow
+            // symlinks and usually come straight from the directory listing.
+            if let Ok(file_type) = entry.file_type() {
+                if file_type.is_symlink() {
+                    continue;
+                }
+
+                let p = entry.path();
+                if ctx.is_excluded(&p, file_type.is_dir()) {
+                    continue;
+                }
+
+                if file_type.is_file() {
+                    if let Ok(meta) = entry.metadata() {
+                        totals = totals + ctx.file_totals(&meta);
+                        totals.files += 1;
+                    }
+                } else if file_type.is_dir() {
+                    if let Ok(meta) = entry.metadata() {
+                        if !ctx.crosses_mount(&p, &meta) {
+                            subdirs.push((p, DirTotals::of(&meta)));
+                            totals.dirs += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    totals = totals
+        + subdirs
+            .par_iter()
+            .map(|(p, dir_size)| calculate_dir_size(p, *dir_size, ctx))
+            .reduce(DirTotals::default, |a, b| a + b);
+
+    if !ctx.cancel.load(Ordering::Relaxed) {
+        (ctx.sink)(ScanEvent::Scanned(FileEntry {
+            file: path.to_str().unwrap_or_default().to_string(),
+            size: totals.size,
+            apparent_size: totals.apparent_size,
+            files: totals.files,
+            dirs: totals.dirs,
+            accessed: None,
+            excluded: false,
+        }));
+    }
+    totals
+}
+
+/// Scans every folder below `start_dir` on the calling thread's rayon pool,
+/// reporting each folder to `sink` once all of its contents have been sized.
+/// Use [`start`] to run a scan in the background instead.
+pub fn walk_dirs(
+    start_dir: &Path,
+    options: &ScanOptions,
+    cancel: &AtomicBool,
+    sink: &(dyn Fn(ScanEvent) + Sync),
+) {
+    use std::fs;
+
+    // Each worker recurses into subfolders, so give deep trees some headroom.
+    let pool = match rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads)
+        .stack_size(16 * 1024 * 1024)
+        .build()
+    {
+        Ok(pool) => pool,
+        Err(_) => return,
+    };
+
+    let root_device = if options.one_file_system {
+        fs::metadata(start_dir)
+            .ok()
+            .and_then(|meta| device_id(&meta))
+    } else {
+        None
+    };
+    let ctx = ScanContext {
+        options,
+        cancel,
+        sink,
+        seen_inodes: Mutex::new(HashSet::new()),
+        root_device,
+    };
+
+    let mut dirs = Vec::new();
+    if let Ok(dir_entries) = fs::read_dir(start_dir) {
+        for entry in dir_entries.flatten() {
+            let path = entry.path();
+            if let Ok(meta) = fs::metadata(&path) {
+                if meta.is_dir()
+                    && !ctx.is_excluded(&path, true)
+                    && !ctx.crosses_mount(&path, &meta)
+                {
+                    dirs.push((path, DirTotals::of(&meta)));
+                }
+            }
+        }
+    }
+    pool.install(|| {
+        dirs.par_iter().for_each(|(dir, dir_size)| {
+            calculate_dir_size(dir, *dir_size, &ctx);
+        })
+    });
+}
+
+/// A scan running on a background thread. Dropping it cancels the scan.
+pub struct Scan {
+    events: mpsc::UnboundedReceiver<ScanEvent>,
+    cancel: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+#[derive(Default)]
+pub struct ScanResults {
+    pub entries: Vec<FileEntry>,
+    pub skipped_mounts: Vec<PathBuf>,
+    pub cancelled: bool,
+}
+
+pub fn start(root: &Path, options: ScanOptions) -> Scan {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (event_tx, events) = mpsc::unbounded();
+    let worker = {
+        let root = root.to_path_buf();
+        let cancel = cancel.clone();
+        std::thread::spawn(move || {
+            walk_dirs(&root, &options, &cancel, &|event| {
+                let _ = event_tx.unbounded_send(event);
+            });
+        })
+    };
+
+    Scan {
+        events,
+        cancel,
+        worker: Some(worker),
+    }
+}
+
+impl Scan {
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until the scan has finished and returns everything it found.
+    pub fn wait(mut self) -> ScanResults {
+        let mut results = ScanResults::default();
+        while let Some(event) = futures::executor::block_on(self.events.next()) {
+            match event {
+                ScanEvent::Scanned(entry) => results.entries.push(entry),
+                ScanEvent::SkippedMount(path) => results.skipped_mounts.push(path),
+            }
+        }
+        results.cancelled = self.is_cancelled();
+        results
+    }
+}
+
+impl Stream for Scan {
+    type Item = ScanEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ScanEvent>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for Scan {
+    fn drop(&mut self) {
+        self.cancel();
+        self.events.close();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn build_tree(root: &Path) {
+        for a in 0..4 {
+            for b in 0..3 {
+                let dir = root.join(format!("a{}", a)).join(format!("b{}", b));
+                fs::create_dir_all(&dir).unwrap();
+                for f in 0..3 {
+                    fs::write(
+                        dir.join(format!("f{}", f)),
+                        vec![0u8; 1000 * (a + b + f + 1)],
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+
+    fn scan(root: &Path, options: ScanOptions) -> Vec<(String, u64, u64, u64, u64)> {
+        let mut entries: Vec<_> = start(root, options)
+            .wait()
+            .entries
+            .into_iter()
+            .map(|e| (e.file, e.size, e.apparent_size, e.files, e.dirs))
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn thread_count_does_not_change_totals() {
+        let dir = tempfile::tempdir().unwrap();
+        build_tree(dir.path());
+        let single = scan(
+            dir.path(),
+            ScanOptions {
+                threads: 1,
+                ..ScanOptions::default()
+            },
+        );
+        let parallel = scan(
+            dir.path(),
+            ScanOptions {
+                threads: std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(4),
+                ..ScanOptions::default()
+            },
+        );
+        assert_eq!(single.len(), 16);
+        assert_eq!(single, parallel);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once_or_per_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let top = dir.path().join("top");
+        fs::create_dir_all(top.join("a")).unwrap();
+        fs::create_dir_all(top.join("b")).unwrap();
+        fs::write(top.join("a/file"), vec![0u8; 10_000]).unwrap();
+        fs::hard_link(top.join("a/file"), top.join("b/link")).unwrap();
+
+        // Folders count their own size too.
+        let folders: u64 = ["", "a", "b"]
+            .iter()
+            .map(|sub| fs::metadata(top.join(sub)).unwrap().len())
+            .sum();
+        let top_size = |hard_links| {
+            let entries = scan(
+                dir.path(),
+                ScanOptions {
+                    hard_links,
+                    ..ScanOptions::default()
+                },
+            );
+            let top = top.to_str().unwrap();
+            let (_, _, apparent, files, _) = entries.into_iter().find(|e| e.0 == top).unwrap();
+            (apparent - folders, files)
+        };
+        assert_eq!(top_size(HardLinkMode::CountOnce), (10_000, 2));
+        assert_eq!(top_size(HardLinkMode::CountEveryLink), (20_000, 2));
+    }
+
+    fn rules(patterns: &[&str]) -> ExclusionRules {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        ExclusionRules::new(&patterns).unwrap()
+    }
+
+    #[test]
+    fn name_globs_match_the_file_name_anywhere() {
+        let rules = rules(&["node_modules", "*.tmp"]);
+        assert!(rules.is_excluded(Path::new("/home/me/app/node_modules")));
+        assert!(rules.is_excluded(Path::new("/data/cache.tmp")));
+        assert!(!rules.is_excluded(Path::new("/home/me/node_modules_backup")));
+    }
+
+    #[test]
+    fn path_globs_match_the_full_path() {
+        let rules = rules(&["/home/*/.cache"]);
+        assert!(rules.is_excluded(Path::new("/home/me/.cache")));
+        assert!(!rules.is_excluded(Path::new("/srv/me/.cache")));
+    }
+
+    #[test]
+    fn regexes_match_the_full_path() {
+        let rules = rules(&["re:/target/(debug|release)$"]);
+        assert!(rules.is_excluded(Path::new("/src/app/target/debug")));
+        assert!(!rules.is_excluded(Path::new("/src/app/target/doc")));
+    }
+
+    #[test]
+    fn no_rules_exclude_nothing() {
+        assert!(!ExclusionRules::default().is_excluded(Path::new("/anything")));
+        assert!(ExclusionRules::new(&["re:(".to_string()]).is_err());
+    }
+
+    fn entry(file: &str, size: u64, files: u64, dirs: u64) -> FileEntry {
+        FileEntry {
+            file: file.to_string(),
+            size,
+            apparent_size: size,
+            files,
+            dirs,
+            accessed: None,
+            excluded: false,
+        }
+    }
+
+    #[test]
+    fn remove_folder_updates_ancestors() {
+        let mut entries = vec![
+            entry("/r/a", 100, 10, 3),
+            entry("/r/a/b", 60, 6, 1),
+            entry("/r/a/b/c", 20, 2, 0),
+            entry("/r/a/d", 30, 3, 0),
+            entry("/r/ab", 5, 1, 0),
+        ];
+        let removed = remove_folder(&mut entries, Path::new("/r/a/b")).unwrap();
+        assert_eq!(removed.size, 60);
+
+        let files: Vec<&str> = entries.iter().map(|e| e.file.as_str()).collect();
+        assert_eq!(files, ["/r/a", "/r/a/d", "/r/ab"]);
+        assert_eq!(
+            (entries[0].size, entries[0].files, entries[0].dirs),
+            (40, 4, 1)
+        );
+        // A sibling that only shares a name prefix is left alone.
+        assert_eq!(entries[2].size, 5);
+        assert!(remove_folder(&mut entries, Path::new("/r/missing")).is_none());
+    }
+}
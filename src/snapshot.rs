@@ -0,0 +1,141 @@
+//! Complete scans saved to disk so they can be reopened without rescanning.
+//!
+//! A snapshot is gzip-compressed JSON holding every scanned folder together
+//! with the options the scan ran with.
+
+use crate::scanner::{FileEntry, ScanOptions};
+use chrono::{DateTime, Local};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub const EXTENSION: &str = "fbfsnap";
+
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    version: u32,
+    pub root: PathBuf,
+    pub scanned_at: DateTime<Local>,
+    /// `None` for scans imported from elsewhere.
+    #[serde(default)]
+    pub options: Option<ScanOptions>,
+    pub skipped_mounts: Vec<String>,
+    pub entries: Vec<FileEntry>,
+}
+
+impl Snapshot {
+    pub fn new(
+        root: PathBuf,
+        scanned_at: DateTime<Local>,
+        options: Option<ScanOptions>,
+        skipped_mounts: Vec<String>,
+        entries: Vec<FileEntry>,
+    ) -> Self {
+        Self {
+            version: VERSION,
+            root,
+            scanned_at,
+            options,
+            skipped_mounts,
+            entries,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(path)?), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+        let snapshot: Self = serde_json::from_reader(decoder)?;
+        if snapshot.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::HardLinkMode;
+
+    fn snapshot() -> Snapshot {
+        Snapshot::new(
+            PathBuf::from("/r"),
+            Local::now(),
+            Some(ScanOptions {
+                threads: 2,
+                hard_links: HardLinkMode::CountEveryLink,
+                ..ScanOptions::default()
+            }),
+            vec!["/r/mnt".to_string()],
+            vec![FileEntry {
+                file: "/r/a".to_string(),
+                size: 4096,
+                apparent_size: 100,
+                files: 2,
+                dirs: 1,
+                accessed: Some(Local::now()),
+                excluded: false,
+            }],
+        )
+    }
+
+    #[test]
+    fn snapshots_load_back_as_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("scan.{}", EXTENSION));
+        let saved = snapshot();
+        saved.save(&path).unwrap();
+
+        let loaded = Snapshot::load(&path).unwrap();
+        assert_eq!(loaded.root, saved.root);
+        assert_eq!(loaded.scanned_at, saved.scanned_at);
+        assert_eq!(loaded.skipped_mounts, saved.skipped_mounts);
+        let options = loaded.options.unwrap();
+        assert_eq!(
+            (options.threads, options.hard_links),
+            (2, HardLinkMode::CountEveryLink)
+        );
+        let (entry, original) = (&loaded.entries[0], &saved.entries[0]);
+        assert_eq!(
+            (
+                &entry.file,
+                entry.size,
+                entry.apparent_size,
+                entry.files,
+                entry.dirs
+            ),
+            (&original.file, 4096, 100, 2, 1)
+        );
+        assert_eq!(entry.accessed, original.accessed);
+    }
+
+    #[test]
+    fn snapshots_from_other_versions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("scan.{}", EXTENSION));
+        let mut json = serde_json::to_value(snapshot()).unwrap();
+        json["version"] = (VERSION + 1).into();
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        serde_json::to_writer(&mut encoder, &json).unwrap();
+        encoder.finish().unwrap();
+
+        let error = Snapshot::load(&path).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unsupported snapshot version"));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::app::Message;
+use crate::treemap::{draw_tooltip, palette, shade};
+use du_gui_rs::format_size;
+use du_gui_rs::tree::ScanTree;
+use iced::mouse;
+use iced::widget::canvas::{self, event, Frame, Geometry, Path, Stroke};
+use iced::{Color, Point, Rectangle, Renderer, Size, Theme};
+use std::f32::consts::TAU;
+
+const RINGS: usize = 4;
+const MIN_ANGLE: f32 = 0.005;
+
+struct Segment {
+    node: usize,
+    depth: usize,
+    start: f32,
+    end: f32,
+    color: Color,
+}
+
+/// Radial view of the folders below `root`, one ring per level.
+pub struct Sunburst<'a> {
+    pub tree: &'a ScanTree,
+    pub root: usize,
+}
+
+struct Rings {
+    center: Point,
+    inner_radius: f32,
+    ring_width: f32,
+}
+
+impl Sunburst<'_> {
+    fn rings(&self, size: Size) -> Rings {
+        let radius = (size.width.min(size.height) / 2.0 - 4.0).max(0.0);
+        let inner_radius = radius / (RINGS as f32 + 1.0);
+        Rings {
+            center: Point::new(size.width / 2.0, size.height / 2.0),
+            inner_radius,
+            ring_width: (radius - inner_radius) / RINGS as f32,
+        }
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        self.layout_children(self.root, 0.0, TAU, 1, None, &mut segments);
+        segments
+    }
+
+    fn layout_children(
+        &self,
+        parent: usize,
+        start: f32,
+        end: f32,
+        depth: usize,
+        color: Option<Color>,
+        segments: &mut Vec<Segment>,
+    ) {
+        let parent_size = self.tree.node(parent).size;
+        if depth > RINGS || parent_size == 0 {
+            return;
+        }
+
+        let mut angle = start;
+        for (i, &child) in self.tree.node(parent).children.iter().enumerate() {
+            let sweep = (end - start) * self.tree.node(child).size as f32 / parent_size as f32;
+            if sweep < MIN_ANGLE {
+                // Children are sorted by size, so the rest are even smaller.
+                break;
+            }
+            let color = match color {
+                Some(parent_color) => shade(parent_color, 0.85),
+                None => palette(i),
+            };
+            segments.push(Segment {
+                node: child,
+                depth,
+                start: angle,
+                end: angle + sweep,
+                color,
+            });
+            self.layout_children(
+                child,
+                angle,
+                angle + sweep,
+                depth + 1,
+                Some(color),
+                segments,
+            );
+            angle += sweep;
+        }
+    }
+
+    fn hovered<'s>(
+        &self,
+        segments: &'s [Segment],
+        rings: &Rings,
+        position: Point,
+    ) -> Option<&'s Segment> {
+        let dx = position.x - rings.center.x;
+        let dy = position.y - rings.center.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance < rings.inner_radius {
+            return None;
+        }
+        let depth = ((distance - rings.inner_radius) / rings.ring_width) as usize + 1;
+        let angle = dy.atan2(dx).rem_euclid(TAU);
+        segments
+            .iter()
+            .find(|s| s.depth == depth && angle >= s.start && angle < s.end)
+    }
+
+    fn on_center(&self, rings: &Rings, position: Point) -> bool {
+        position.distance(rings.center) < rings.inner_radius
+    }
+}
+
+impl canvas::Program<Message> for Sunburst<'_> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        if let canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event {
+            if let Some(position) = cursor.position_in(bounds) {
+                let rings = self.rings(bounds.size());
+                if self.on_center(&rings, position) {
+                    if let Some(parent) = self.tree.parent(self.root) {
+                        return (
+                            event::Status::Captured,
+                            Some(Message::SunburstZoom(self.tree.node(parent).path.clone())),
+                        );
+                    }
+                } else if let Some(segment) = self.hovered(&self.segments(), &rings, position) {
+                    let node = self.tree.node(segment.node);
+                    if !node.children.is_empty() {
+                        return (
+                            event::Status::Captured,
+                            Some(Message::SunburstZoom(node.path.clone())),
+                        );
+                    }
+                }
+            }
+        }
+        (event::Status::Ignored, None)
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let rings = self.rings(bounds.size());
+        let segments = self.segments();
+        let background = theme.palette().background;
+
+        frame.fill(
+            &Path::circle(rings.center, rings.inner_radius),
+            theme.palette().primary,
+        );
+        for segment in &segments {
+            let path = annular_sector(&rings, segment);
+            frame.fill(&path, segment.color);
+            frame.stroke(
+                &path,
+                Stroke::default().with_color(background).with_width(1.0),
+            );
+        }
+
+        if let Some(position) = cursor.position_in(bounds) {
+            let root = self.tree.node(self.root);
+            if self.on_center(&rings, position) {
+                draw_tooltip(
+                    &mut frame,
+                    bounds.size(),
+                    position,
+                    &[
+                        root.path.to_string_lossy().to_string(),
+                        format_size(root.size),
+                    ],
+                );
+            } else if let Some(segment) = self.hovered(&segments, &rings, position) {
+                let node = self.tree.node(segment.node);
+                frame.stroke(
+                    &annular_sector(&rings, segment),
+                    Stroke::default().with_color(Color::WHITE).with_width(2.0),
+                );
+                let percent = if root.size > 0 {
+                    node.size as f64 * 100.0 / root.size as f64
+                } else {
+                    0.0
+                };
+                draw_tooltip(
+                    &mut frame,
+                    bounds.size(),
+                    position,
+                    &[
+                        node.path.to_string_lossy().to_string(),
+                        format!("{} ({:.1}%)", format_size(node.size), percent),
+                    ],
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+fn annular_sector(rings: &Rings, segment: &Segment) -> Path {
+    let inner = rings.inner_radius + (segment.depth - 1) as f32 * rings.ring_width;
+    let outer = inner + rings.ring_width;
+    let steps = ((segment.end - segment.start) / 0.05).ceil().max(1.0) as usize;
+    let point = |radius: f32, angle: f32| {
+        Point::new(
+            rings.center.x + radius * angle.cos(),
+            rings.center.y + radius * angle.sin(),
+        )
+    };
+
+    Path::new(|builder| {
+        builder.move_to(point(outer, segment.start));
+        for i in 1..=steps {
+            let angle = segment.start + (segment.end - segment.start) * i as f32 / steps as f32;
+            builder.line_to(point(outer, angle));
+        }
+        for i in (0..=steps).rev() {
+            let angle = segment.start + (segment.end - segment.start) * i as f32 / steps as f32;
+            builder.line_to(point(inner, angle));
+        }
+        builder.close();
+    })
+}
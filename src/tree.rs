@@ -0,0 +1,411 @@
+//! Folder hierarchy rebuilt from a flat scan, and the treemap laid out on it.
+
+use crate::scanner::{FileEntry, SizeMode};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub struct TreeNode {
+    pub path: PathBuf,
+    pub name: String,
+    pub size: u64,
+    pub children: Vec<usize>,
+}
+
+/// Folder hierarchy rebuilt from the flat list of scanned entries. The scan
+/// root is always node 0 and children are sorted by size, biggest first.
+#[derive(Default)]
+pub struct ScanTree {
+    nodes: Vec<TreeNode>,
+    index: HashMap<PathBuf, usize>,
+}
+
+impl ScanTree {
+    pub fn build(root: &Path, entries: &[FileEntry], size_mode: SizeMode) -> Self {
+        let mut nodes = vec![TreeNode {
+            path: root.to_path_buf(),
+            name: root.to_string_lossy().to_string(),
+            size: 0,
+            children: Vec::new(),
+        }];
+        let mut index = HashMap::new();
+        index.insert(root.to_path_buf(), 0);
+        let mut root_size = None;
+
+        for entry in entries {
+            let path = PathBuf::from(&entry.file);
+            if path == root {
+                root_size = Some(entry.size_for(size_mode));
+                continue;
+            }
+            if index.contains_key(&path) {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| entry.file.clone());
+            index.insert(path.clone(), nodes.len());
+            nodes.push(TreeNode {
+                path,
+                name,
+                size: entry.size_for(size_mode),
+                children: Vec::new(),
+            });
+        }
+
+        // A stopped scan can leave folders without their parent, so hang
+        // those off the closest ancestor that made it into the tree.
+        for i in 1..nodes.len() {
+            let parent = nodes[i]
+                .path
+                .ancestors()
+                .skip(1)
+                .find_map(|ancestor| index.get(ancestor).copied())
+                .unwrap_or(0);
+            nodes[parent].children.push(i);
+        }
+
+        let mut tree = Self { nodes, index };
+        // Without an entry of its own, files directly in the root go uncounted.
+        tree.nodes[0].size = root_size.unwrap_or_else(|| {
+            tree.nodes[0]
+                .children
+                .iter()
+                .map(|&child| tree.nodes[child].size)
+                .sum()
+        });
+        for i in 0..tree.nodes.len() {
+            let mut children = std::mem::take(&mut tree.nodes[i].children);
+            children.sort_by(|&a, &b| tree.nodes[b].size.cmp(&tree.nodes[a].size));
+            tree.nodes[i].children = children;
+        }
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    pub fn node(&self, id: usize) -> &TreeNode {
+        &self.nodes[id]
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.index.get(path).copied()
+    }
+
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        if id == self.root() {
+            return None;
+        }
+        self.nodes[id]
+            .path
+            .ancestors()
+            .skip(1)
+            .find_map(|ancestor| self.find(ancestor))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LayoutRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl LayoutRect {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+/// Base colors for top-level treemap cells, as RGB fractions.
+pub const PALETTE: [[f32; 3]; 8] = [
+    [0.55, 0.71, 0.91],
+    [0.65, 0.85, 0.54],
+    [0.94, 0.62, 0.49],
+    [0.79, 0.62, 0.90],
+    [0.95, 0.80, 0.47],
+    [0.51, 0.84, 0.80],
+    [0.93, 0.60, 0.75],
+    [0.73, 0.75, 0.80],
+];
+
+/// Room left above nested cells for their parent's name.
+pub const LABEL_HEIGHT: f32 = 16.0;
+
+const TREEMAP_DEPTH: usize = 2;
+const MAX_CHILDREN: usize = 200;
+
+pub struct TreemapCell {
+    pub node: usize,
+    pub rect: LayoutRect,
+    /// 1 for the children of the treemap's root.
+    pub depth: usize,
+    /// Index into [`PALETTE`], shared by a top-level cell and everything
+    /// nested in it.
+    pub color: usize,
+}
+
+/// Treemap of the folders below `root`, two levels deep, parents before
+/// their children. A folder's own files and any children left out get empty
+/// space, so every area stays proportional to its size.
+pub fn treemap(tree: &ScanTree, root: usize, bounds: LayoutRect) -> Vec<TreemapCell> {
+    let mut cells = Vec::new();
+    treemap_children(tree, root, bounds, 1, None, &mut cells);
+    cells
+}
+
+fn treemap_children(
+    tree: &ScanTree,
+    parent: usize,
+    bounds: LayoutRect,
+    depth: usize,
+    color: Option<usize>,
+    cells: &mut Vec<TreemapCell>,
+) {
+    let children: Vec<usize> = tree
+        .node(parent)
+        .children
+        .iter()
+        .copied()
+        .filter(|&child| tree.node(child).size > 0)
+        .take(MAX_CHILDREN)
+        .collect();
+    let mut sizes: Vec<u64> = children
+        .iter()
+        .map(|&child| tree.node(child).size)
+        .collect();
+    let rest = tree.node(parent).size.saturating_sub(sizes.iter().sum());
+    let filler = sizes
+        .iter()
+        .position(|&size| size < rest)
+        .unwrap_or(sizes.len());
+    if rest > 0 {
+        sizes.insert(filler, rest);
+    }
+    let mut rects = squarify(&sizes, bounds);
+    if rest > 0 {
+        rects.remove(filler);
+    }
+
+    for (i, (child, rect)) in children.into_iter().zip(rects).enumerate() {
+        let color = color.unwrap_or(i % PALETTE.len());
+        cells.push(TreemapCell {
+            node: child,
+            rect,
+            depth,
+            color,
+        });
+
+        if depth < TREEMAP_DEPTH && rect.width > 40.0 && rect.height > LABEL_HEIGHT + 20.0 {
+            let inner = LayoutRect {
+                x: rect.x + 2.0,
+                y: rect.y + LABEL_HEIGHT,
+                width: rect.width - 4.0,
+                height: rect.height - LABEL_HEIGHT - 2.0,
+            };
+            treemap_children(tree, child, inner, depth + 1, Some(color), cells);
+        }
+    }
+}
+
+/// Squarified treemap layout (Bruls, Huizing & van Wijk). `sizes` should be
+/// sorted biggest first; the returned rectangles follow the same order.
+pub fn squarify(sizes: &[u64], bounds: LayoutRect) -> Vec<LayoutRect> {
+    let total: f64 = sizes.iter().map(|&s| s as f64).sum();
+    if total <= 0.0 || bounds.width <= 0.0 || bounds.height <= 0.0 {
+        return vec![LayoutRect::default(); sizes.len()];
+    }
+
+    let scale = bounds.width as f64 * bounds.height as f64 / total;
+    let areas: Vec<f64> = sizes.iter().map(|&s| s as f64 * scale).collect();
+
+    let (mut x, mut y) = (bounds.x as f64, bounds.y as f64);
+    let (mut width, mut height) = (bounds.width as f64, bounds.height as f64);
+    let mut rects = Vec::with_capacity(sizes.len());
+    let mut start = 0;
+    while start < areas.len() {
+        let side = width.min(height);
+        let mut end = start + 1;
+        let mut row_area = areas[start];
+        let mut best = worst_ratio(&areas[start..end], row_area, side);
+        while end < areas.len() {
+            let ratio = worst_ratio(&areas[start..=end], row_area + areas[end], side);
+            if ratio > best {
+                break;
+            }
+            best = ratio;
+            row_area += areas[end];
+            end += 1;
+        }
+
+        if width >= height {
+            let row_width = if height > 0.0 { row_area / height } else { 0.0 };
+            let mut cy = y;
+            for &area in &areas[start..end] {
+                let h = if row_width > 0.0 {
+                    area / row_width
+                } else {
+                    0.0
+                };
+                rects.push(LayoutRect {
+                    x: x as f32,
+                    y: cy as f32,
+                    width: row_width as f32,
+                    height: h as f32,
+                });
+                cy += h;
+            }
+            x += row_width;
+            width -= row_width;
+        } else {
+            let row_height = if width > 0.0 { row_area / width } else { 0.0 };
+            let mut cx = x;
+            for &area in &areas[start..end] {
+                let w = if row_height > 0.0 {
+                    area / row_height
+                } else {
+                    0.0
+                };
+                rects.push(LayoutRect {
+                    x: cx as f32,
+                    y: y as f32,
+                    width: w as f32,
+                    height: row_height as f32,
+                });
+                cx += w;
+            }
+            y += row_height;
+            height -= row_height;
+        }
+        start = end;
+    }
+    rects
+}
+
+fn worst_ratio(row: &[f64], row_area: f64, side: f64) -> f64 {
+    let max = row.iter().cloned().fold(0.0, f64::max);
+    let min = row.iter().cloned().fold(f64::INFINITY, f64::min);
+    let area2 = row_area * row_area;
+    let side2 = side * side;
+    f64::max(side2 * max / area2, area2 / (side2 * min))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(rect: &LayoutRect) -> f32 {
+        rect.width * rect.height
+    }
+
+    #[test]
+    fn squarify_areas_are_proportional_and_fill_the_bounds() {
+        let bounds = LayoutRect {
+            x: 10.0,
+            y: 20.0,
+            width: 600.0,
+            height: 400.0,
+        };
+        let sizes = [500, 250, 125, 75, 50];
+        let rects = squarify(&sizes, bounds);
+        assert_eq!(rects.len(), sizes.len());
+
+        let total: u64 = sizes.iter().sum();
+        for (size, rect) in sizes.iter().zip(&rects) {
+            let expected = area(&bounds) * *size as f32 / total as f32;
+            assert!((area(rect) - expected).abs() < 1.0);
+            assert!(rect.x >= bounds.x - 0.01 && rect.y >= bounds.y - 0.01);
+            assert!(rect.x + rect.width <= bounds.x + bounds.width + 0.01);
+            assert!(rect.y + rect.height <= bounds.y + bounds.height + 0.01);
+        }
+    }
+
+    #[test]
+    fn squarify_handles_nothing_to_lay_out() {
+        let bounds = LayoutRect {
+            width: 100.0,
+            height: 100.0,
+            ..LayoutRect::default()
+        };
+        assert!(squarify(&[], bounds).is_empty());
+        assert_eq!(squarify(&[0, 0], bounds), vec![LayoutRect::default(); 2]);
+    }
+
+    fn entry(file: &str, size: u64) -> FileEntry {
+        FileEntry {
+            file: file.to_string(),
+            size,
+            apparent_size: size,
+            files: 0,
+            dirs: 0,
+            accessed: None,
+            excluded: false,
+        }
+    }
+
+    fn children(tree: &ScanTree, path: &str) -> Vec<PathBuf> {
+        let id = tree.find(Path::new(path)).unwrap();
+        tree.node(id)
+            .children
+            .iter()
+            .map(|&child| tree.node(child).path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn orphans_hang_off_their_closest_ancestor() {
+        // A stopped scan reported /r/a/b/c but never /r/a/b.
+        let entries = [entry("/r/a", 50), entry("/r/a/b/c", 20), entry("/r/x/y", 5)];
+        let tree = ScanTree::build(Path::new("/r"), &entries, SizeMode::OnDisk);
+        assert_eq!(children(&tree, "/r/a"), [PathBuf::from("/r/a/b/c")]);
+        assert_eq!(
+            children(&tree, "/r"),
+            [PathBuf::from("/r/a"), PathBuf::from("/r/x/y")]
+        );
+        let orphan = tree.find(Path::new("/r/a/b/c")).unwrap();
+        assert_eq!(tree.parent(orphan), tree.find(Path::new("/r/a")));
+    }
+
+    #[test]
+    fn root_size_includes_its_own_files_when_known() {
+        let children = [entry("/r/a", 30), entry("/r/b", 20)];
+        let tree = ScanTree::build(Path::new("/r"), &children, SizeMode::OnDisk);
+        assert_eq!(tree.node(tree.root()).size, 50);
+
+        let mut entries = children.to_vec();
+        entries.push(entry("/r", 80));
+        let tree = ScanTree::build(Path::new("/r"), &entries, SizeMode::OnDisk);
+        assert_eq!(tree.node(tree.root()).size, 80);
+        assert_eq!(tree.node(tree.root()).children.len(), 2);
+    }
+
+    #[test]
+    fn treemap_leaves_room_for_a_folders_own_files() {
+        let entries = [entry("/r/a", 60), entry("/r/a/c", 30), entry("/r/b", 40)];
+        let tree = ScanTree::build(Path::new("/r"), &entries, SizeMode::OnDisk);
+        let bounds = LayoutRect {
+            width: 600.0,
+            height: 400.0,
+            ..LayoutRect::default()
+        };
+        let cells = treemap(&tree, tree.root(), bounds);
+        let cell = |path: &str| {
+            let id = tree.find(Path::new(path)).unwrap();
+            cells.iter().find(|cell| cell.node == id).unwrap()
+        };
+
+        let a = cell("/r/a");
+        assert!((area(&a.rect) - area(&bounds) * 0.6).abs() < 1.0);
+        let inner = (a.rect.width - 4.0) * (a.rect.height - LABEL_HEIGHT - 2.0);
+        let c = cell("/r/a/c");
+        assert_eq!((c.depth, c.color), (2, a.color));
+        assert!((area(&c.rect) - inner * 0.5).abs() < 1.0);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::app::Message;
+use du_gui_rs::format_size;
+use du_gui_rs::tree::{self, LayoutRect, ScanTree, TreemapCell, LABEL_HEIGHT};
+use iced::mouse;
+use iced::widget::canvas::{self, event, Frame, Geometry, Path, Stroke, Text};
+use iced::{Color, Pixels, Point, Rectangle, Renderer, Size, Theme};
+
+/// Squarified treemap of the folders below `root`, two levels deep.
+pub struct Treemap<'a> {
+    pub tree: &'a ScanTree,
+    pub root: usize,
+}
+
+impl Treemap<'_> {
+    fn layout(&self, size: Size) -> Vec<TreemapCell> {
+        let bounds = LayoutRect {
+            x: 0.0,
+            y: 0.0,
+            width: size.width,
+            height: size.height,
+        };
+        tree::treemap(self.tree, self.root, bounds)
+    }
+
+    fn hovered<'c>(&self, cells: &'c [TreemapCell], position: Point) -> Option<&'c TreemapCell> {
+        cells
+            .iter()
+            .filter(|cell| cell.rect.contains(position.x, position.y))
+            .max_by_key(|cell| cell.depth)
+    }
+}
+
+impl canvas::Program<Message> for Treemap<'_> {
+    type State = ();
+
+    fn update(
+        &self,
+        _state: &mut Self::State,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<Message>) {
+        if let canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event {
+            if let Some(position) = cursor.position_in(bounds) {
+                let cells = self.layout(bounds.size());
+                if let Some(cell) = self.hovered(&cells, position) {
+                    let node = self.tree.node(cell.node);
+                    if !node.children.is_empty() {
+                        return (
+                            event::Status::Captured,
+                            Some(Message::TreemapDrill(node.path.clone())),
+                        );
+                    }
+                }
+            }
+        }
+        (event::Status::Ignored, None)
+    }
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let cells = self.layout(bounds.size());
+
+        for cell in &cells {
+            let top_left = Point::new(cell.rect.x, cell.rect.y);
+            let size = Size::new(cell.rect.width, cell.rect.height);
+            let color = shade(palette(cell.color), 0.85_f32.powi(cell.depth as i32 - 1));
+            frame.fill_rectangle(top_left, size, color);
+            frame.stroke(
+                &Path::rectangle(top_left, size),
+                Stroke::default()
+                    .with_color(shade(color, 0.5))
+                    .with_width(1.0),
+            );
+            if cell.rect.width > 30.0 && cell.rect.height > LABEL_HEIGHT {
+                let max_chars = ((cell.rect.width - 6.0) / 7.0) as usize;
+                frame.fill_text(Text {
+                    content: truncate(&self.tree.node(cell.node).name, max_chars),
+                    position: Point::new(cell.rect.x + 3.0, cell.rect.y + 2.0),
+                    color: Color::BLACK,
+                    size: Pixels(12.0),
+                    ..Text::default()
+                });
+            }
+        }
+
+        if let Some(position) = cursor.position_in(bounds) {
+            if let Some(cell) = self.hovered(&cells, position) {
+                let node = self.tree.node(cell.node);
+                frame.stroke(
+                    &Path::rectangle(
+                        Point::new(cell.rect.x, cell.rect.y),
+                        Size::new(cell.rect.width, cell.rect.height),
+                    ),
+                    Stroke::default().with_color(Color::WHITE).with_width(2.0),
+                );
+                draw_tooltip(
+                    &mut frame,
+                    bounds.size(),
+                    position,
+                    &[
+                        node.path.to_string_lossy().to_string(),
+                        format_size(node.size),
+                    ],
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if cursor.is_over(bounds) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+pub fn draw_tooltip(frame: &mut Frame, bounds: Size, position: Point, lines: &[String]) {
+    const LINE_HEIGHT: f32 = 16.0;
+    const CHAR_WIDTH: f32 = 7.0;
+
+    let width =
+        lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as f32 * CHAR_WIDTH + 12.0;
+    let height = lines.len() as f32 * LINE_HEIGHT + 8.0;
+    let x = (position.x + 12.0).min(bounds.width - width).max(0.0);
+    let y = (position.y + 12.0).min(bounds.height - height).max(0.0);
+
+    frame.fill_rectangle(
+        Point::new(x, y),
+        Size::new(width, height),
+        Color::from_rgba(0.0, 0.0, 0.0, 0.8),
+    );
+    for (i, line) in lines.iter().enumerate() {
+        frame.fill_text(Text {
+            content: line.clone(),
+            position: Point::new(x + 6.0, y + 4.0 + i as f32 * LINE_HEIGHT),
+            color: Color::WHITE,
+            size: Pixels(13.0),
+            ..Text::default()
+        });
+    }
+}
+
+pub fn palette(index: usize) -> Color {
+    let [r, g, b] = tree::PALETTE[index % tree::PALETTE.len()];
+    Color::from_rgb(r, g, b)
+}
+
+pub fn shade(color: Color, factor: f32) -> Color {
+    Color::from_rgb(color.r * factor, color.g * factor, color.b * factor)
+}
+
+fn truncate(name: &str, max_chars: usize) -> String {
+    if name.chars().count() <= max_chars {
+        name.to_string()
+    } else if max_chars > 1 {
+        let mut short: String = name.chars().take(max_chars - 1).collect();
+        short.push('…');
+        short
+    } else {
+        String::new()
+    }
+}